@@ -0,0 +1,262 @@
+use std::slice;
+
+use crate::{escape, PreEscaped, Render};
+
+/// A set of HTML attributes that is only known at runtime.
+///
+/// Attributes can be spread onto an element with the `..(attrs)` syntax.
+/// Anything that iterates over `(name, value)` pairs can be spread, but
+/// collecting into an `Attributes` first lets you build up the set
+/// incrementally.
+///
+/// Values can be anything that implements [`Render`], and are escaped
+/// when they are inserted. Names are compared case-insensitively.
+/// Inserting an attribute that already exists replaces its value, except
+/// for `class` and `style`, whose values are merged.
+///
+/// # Example
+///
+/// ```rust
+/// use maud::{html, Attributes};
+///
+/// let mut attrs = Attributes::new();
+/// attrs.insert("data-id", 42);
+/// attrs.insert("class", "big");
+///
+/// let markup = html! {
+///     div.card ..(attrs) { "hello" }
+/// };
+/// assert_eq!(
+///     markup.into_string(),
+///     r#"<div class="card big" data-id="42">hello</div>"#,
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    /// Attribute names, along with their rendered (escaped) values.
+    entries: Vec<(String, String)>,
+}
+
+impl Attributes {
+    /// Creates an empty set of attributes.
+    pub fn new() -> Attributes {
+        Attributes::default()
+    }
+
+    /// Inserts an attribute.
+    ///
+    /// If the attribute is already present, its value is replaced, or, for
+    /// `class` and `style`, appended to.
+    pub fn insert(&mut self, name: impl AsRef<str>, value: impl Render) {
+        let name = name.as_ref();
+        let value = value.render().into_string();
+        match self.position(name) {
+            Some(index) => {
+                let existing = &mut self.entries[index].1;
+                if is_merged(name) {
+                    merge(name, existing, &value);
+                } else {
+                    *existing = value;
+                }
+            }
+            None => self.entries.push((name.to_owned(), value)),
+        }
+    }
+
+    /// Removes an attribute, returning its value if it was present.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self.position(name)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Returns the rendered value of an attribute.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name)
+            .map(|index| self.entries[index].1.as_str())
+    }
+
+    /// Returns `true` if the attribute is present.
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Returns the number of attributes.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the attributes and their rendered values, in
+    /// insertion order.
+    pub fn iter(&self) -> AttributesIter<'_> {
+        AttributesIter(self.entries.iter())
+    }
+
+    /// Returns `true` if `name` is a valid HTML attribute name.
+    ///
+    /// Valid names are non-empty, and don't contain whitespace, control
+    /// characters, noncharacters, or any of `"'<>/=`. See
+    /// <https://html.spec.whatwg.org/multipage/syntax.html#attributes-2>.
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.chars().all(|c| {
+                !c.is_control()
+                    && !c.is_whitespace()
+                    && !matches!(c, '"' | '\'' | '>' | '<' | '/' | '=')
+                    && !is_noncharacter(c)
+            })
+    }
+
+    /// Appends the value of `name` to an attribute value that is being
+    /// rendered, separating it from what came before if `separate` is set.
+    ///
+    /// `separate` is set if anything was appended. This is used by the
+    /// `html!` macro to merge spread `class` and `style` attributes with the
+    /// ones written in the template.
+    #[doc(hidden)]
+    pub fn render_merged_to(&self, name: &str, separate: &mut bool, w: &mut String) {
+        let value = match self.get(name) {
+            Some(value) if !value.is_empty() => value,
+            _ => return,
+        };
+        if *separate {
+            Attributes::push_separator(name, w);
+        }
+        *separate = true;
+        w.push_str(value);
+    }
+
+    /// Appends the separator between two values of a merged `class` or
+    /// `style` to `w`, which holds the value so far.
+    ///
+    /// This is a space for `class`. For `style` it's a `;`, unless the value
+    /// so far already ends with one. This is used by the `html!` macro.
+    #[doc(hidden)]
+    pub fn push_separator(name: &str, w: &mut String) {
+        if name.eq_ignore_ascii_case("style") {
+            if !w.trim_end().ends_with(';') {
+                w.push(';');
+            }
+        } else {
+            w.push(' ');
+        }
+    }
+
+    /// Renders every attribute except the ones in `skip`.
+    ///
+    /// Attributes whose names aren't valid HTML are left out, so that odd
+    /// keys can't break the surrounding markup.
+    ///
+    /// This is used by the `html!` macro for the `..(attrs)` syntax.
+    #[doc(hidden)]
+    pub fn render_except_to(&self, skip: &[&str], w: &mut String) {
+        for (name, value) in self.iter() {
+            if !Attributes::is_valid_name(name) || skip.iter().any(|s| s.eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+            w.push(' ');
+            escape::escape_to_string(name, w);
+            w.push_str("=\"");
+            w.push_str(value.0);
+            w.push('"');
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+}
+
+impl<K: AsRef<str>, V: Render> Extend<(K, V)> for Attributes {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl<K: AsRef<str>, V: Render> FromIterator<(K, V)> for Attributes {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Attributes {
+        let mut attrs = Attributes::new();
+        attrs.extend(iter);
+        attrs
+    }
+}
+
+impl IntoIterator for Attributes {
+    type Item = (String, PreEscaped<String>);
+    type IntoIter = AttributesIntoIter;
+
+    fn into_iter(self) -> AttributesIntoIter {
+        AttributesIntoIter(self.entries.into_iter())
+    }
+}
+
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = (&'a str, PreEscaped<&'a str>);
+    type IntoIter = AttributesIter<'a>;
+
+    fn into_iter(self) -> AttributesIter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an [`Attributes`].
+#[derive(Debug, Clone)]
+pub struct AttributesIter<'a>(slice::Iter<'a, (String, String)>);
+
+impl<'a> Iterator for AttributesIter<'a> {
+    type Item = (&'a str, PreEscaped<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|(name, value)| (name.as_str(), PreEscaped(value.as_str())))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// An owning iterator over the entries of an [`Attributes`].
+#[derive(Debug)]
+pub struct AttributesIntoIter(std::vec::IntoIter<(String, String)>);
+
+impl Iterator for AttributesIntoIter {
+    type Item = (String, PreEscaped<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(name, value)| (name, PreEscaped(value)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+fn is_noncharacter(c: char) -> bool {
+    let c = c as u32;
+    (0xFDD0..=0xFDEF).contains(&c) || c & 0xFFFE == 0xFFFE
+}
+
+fn is_merged(name: &str) -> bool {
+    name.eq_ignore_ascii_case("class") || name.eq_ignore_ascii_case("style")
+}
+
+fn merge(name: &str, existing: &mut String, value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    if !existing.is_empty() {
+        Attributes::push_separator(name, existing);
+    }
+    existing.push_str(value);
+}
@@ -0,0 +1,50 @@
+//! Support for boolean attributes like `checked=(is_checked)`.
+//!
+//! The `html!` macro can't see the type of a splice, so it wraps the value
+//! in a [`BoolAttr`] and calls [`bool_attr`](ViaBool::bool_attr) on it.
+//! Method resolution picks [`ViaBool`] for anything that borrows as a
+//! `bool`, and falls back to [`ViaRender`] otherwise.
+
+use std::borrow::Borrow;
+
+use crate::Render;
+
+/// The value of a boolean attribute, wrapped by the `html!` macro.
+pub struct BoolAttr<'a, T: ?Sized>(pub &'a T);
+
+/// What to render for a boolean attribute.
+pub enum BoolAttrValue<'a> {
+    /// Render the attribute without a value.
+    On,
+    /// Leave the attribute out.
+    Off,
+    /// Render the attribute with this value.
+    Value(&'a dyn Render),
+}
+
+pub trait ViaBool {
+    fn bool_attr(&self) -> BoolAttrValue<'_>;
+}
+
+impl<'a, T: Borrow<bool> + ?Sized> ViaBool for BoolAttr<'a, T> {
+    fn bool_attr(&self) -> BoolAttrValue<'_> {
+        if *self.0.borrow() {
+            BoolAttrValue::On
+        } else {
+            BoolAttrValue::Off
+        }
+    }
+}
+
+pub trait ViaRender {
+    #[deprecated(
+        note = "boolean attributes are on whenever they have a value; to toggle one with a `bool`, use `name[cond]`"
+    )]
+    fn bool_attr(&self) -> BoolAttrValue<'_>;
+}
+
+impl<'a, T: Render + ?Sized> ViaRender for &BoolAttr<'a, T> {
+    fn bool_attr(&self) -> BoolAttrValue<'_> {
+        BoolAttrValue::Value(&self.0)
+    }
+}
@@ -0,0 +1,27 @@
+//! Support for `@comment`.
+
+use crate::Render;
+
+/// Renders a value that was spliced into a comment.
+///
+/// The value is escaped as usual, and then `<`, `>` and any `-` that could
+/// combine with its neighbours are escaped as well, so that it can't close
+/// the comment early. This holds even for [`PreEscaped`](crate::PreEscaped)
+/// values.
+pub fn render_to<T: Render + ?Sized>(value: &T, w: &mut String) {
+    let mut buffer = String::new();
+    value.render_to(&mut buffer);
+    let mut chars = buffer.chars().peekable();
+    let mut prev = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => w.push_str("&lt;"),
+            '>' => w.push_str("&gt;"),
+            '-' if prev.is_none() || prev == Some('-') || chars.peek().is_none() => {
+                w.push_str("&#45;")
+            }
+            c => w.push(c),
+        }
+        prev = Some(c);
+    }
+}
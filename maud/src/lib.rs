@@ -9,15 +9,26 @@
 
 use std::{
     borrow::Cow,
+    cell::Cell,
     fmt::{self, Arguments, Write},
 };
 
-pub use maud_macros::{html, html_debug};
-
-#[cfg(feature = "zephyr")]
-pub use zephyr;
+pub use maud_macros::{class, html, html_debug};
 
+mod attributes;
+#[doc(hidden)]
+pub mod bool_attr;
+#[doc(hidden)]
+pub mod comment;
 mod escape;
+mod loops;
+mod tag;
+#[cfg(any(feature = "zephyr", feature = "zephyr_build"))]
+pub mod zephyr;
+
+pub use attributes::{Attributes, AttributesIntoIter, AttributesIter};
+pub use loops::{Loop, LoopExt, WithLoop};
+pub use tag::{Tag, TagName};
 
 /// An adapter that escapes HTML special characters.
 ///
@@ -233,6 +244,129 @@ impl<T: AsRef<str>> AsRef<str> for PreEscaped<T> {
     }
 }
 
+/// One or more space-separated class names, for passing to a component.
+///
+/// This renders like a string. With the `zephyr` feature, string literals
+/// in a `Class(...)` argument to a component are registered as classes,
+/// just like the ones written in `class` attributes.
+///
+/// # Example
+///
+/// ```rust
+/// use maud::{html, Class, Markup};
+///
+/// fn badge(label: &str, class: Class) -> Markup {
+///     html! { span.badge class=(class) { (label) } }
+/// }
+///
+/// let markup = html! { @badge("New", Class("px-2 text-red")); };
+/// assert_eq!(
+///     markup.into_string(),
+///     r#"<span class="badge px-2 text-red">New</span>"#,
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Class<'a>(pub &'a str);
+
+impl<'a> Render for Class<'a> {
+    fn render_to(&self, w: &mut String) {
+        str::render_to(self.0, w);
+    }
+}
+
+impl<'a> AsRef<str> for Class<'a> {
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+/// The body of a component call, rendered lazily.
+///
+/// A body written as a closure, e.g. `@card("title") || { ... }`, is
+/// passed as a `Children` rather than as a `Markup`. Splicing it writes
+/// the body straight into the surrounding buffer, so no intermediate
+/// string is allocated.
+///
+/// The body runs when it's rendered, and can only be rendered once, since
+/// it's allowed to move the values it captures. A component that needs the
+/// body more than once should take a `Markup` instead.
+///
+/// # Example
+///
+/// ```rust
+/// use maud::{html, Children, Markup};
+///
+/// fn card(title: &str, children: Children) -> Markup {
+///     html! {
+///         div.card {
+///             h2 { (title) }
+///             (children)
+///         }
+///     }
+/// }
+///
+/// let markup = html! {
+///     @card("Hello") || {
+///         p { "world" }
+///     }
+/// };
+/// assert_eq!(
+///     markup.into_string(),
+///     r#"<div class="card"><h2>Hello</h2><p>world</p></div>"#,
+/// );
+/// ```
+pub struct Children<'a>(Cell<Option<&'a mut dyn RenderOnce>>);
+
+impl<'a> Children<'a> {
+    /// Creates a `Children` from a closure that writes the body into the
+    /// given buffer.
+    ///
+    /// This is used by the `html!` macro; you shouldn't need to call it
+    /// directly.
+    pub fn new<F: FnOnce(&mut String)>(render: &'a mut Option<F>) -> Children<'a> {
+        Children(Cell::new(Some(render)))
+    }
+}
+
+impl<'a> Render for Children<'a> {
+    /// Writes the body into `w`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the body has already been rendered.
+    fn render_to(&self, w: &mut String) {
+        match self.0.take() {
+            Some(render) => render.render_once(w),
+            None => panic!("`maud::Children` can only be rendered once"),
+        }
+    }
+}
+
+impl<'a> From<Children<'a>> for Markup {
+    fn from(children: Children<'a>) -> Markup {
+        children.render()
+    }
+}
+
+impl<'a> fmt::Debug for Children<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Children").finish()
+    }
+}
+
+/// A closure that's called at most once, through a `&mut` reference.
+trait RenderOnce {
+    fn render_once(&mut self, w: &mut String);
+}
+
+impl<F: FnOnce(&mut String)> RenderOnce for Option<F> {
+    fn render_once(&mut self, w: &mut String) {
+        if let Some(render) = self.take() {
+            render(w);
+        }
+    }
+}
+
 /// The literal string `<!DOCTYPE html>`.
 ///
 /// # Example
@@ -0,0 +1,136 @@
+use std::{
+    fmt,
+    iter::{FusedIterator, Peekable},
+};
+
+/// Information about the current iteration of a loop.
+///
+/// This is what the `with` binding in `@for item in items with meta { ... }`
+/// refers to. It can also be obtained outside of `html!` by calling
+/// [`LoopExt::with_loop`] on an iterator.
+///
+/// Note that `loop` is a keyword in Rust, so the binding needs some other
+/// name.
+///
+/// # Example
+///
+/// ```rust
+/// use maud::html;
+///
+/// let markup = html! {
+///     @for name in ["a", "b", "c"] with meta {
+///         (meta.index1) ". " (name)
+///         @if !meta.last { ", " }
+///     }
+/// };
+/// assert_eq!(markup.into_string(), "1. a, 2. b, 3. c");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    /// The index of the current item, starting from 0.
+    pub index: usize,
+    /// The index of the current item, starting from 1.
+    pub index1: usize,
+    /// Whether this is the first item.
+    pub first: bool,
+    /// Whether this is the last item.
+    pub last: bool,
+    /// Whether `index` is even, i.e. this is the 1st, 3rd, ... item.
+    pub even: bool,
+    /// Whether `index` is odd, i.e. this is the 2nd, 4th, ... item.
+    pub odd: bool,
+    /// The total number of items, if the iterator knows it up front.
+    ///
+    /// This is always available for an [`ExactSizeIterator`].
+    pub len: Option<usize>,
+    /// The number of items after this one, if the length is known.
+    pub revindex: Option<usize>,
+}
+
+/// An extension trait that adds [`with_loop`](LoopExt::with_loop) to all
+/// iterators.
+pub trait LoopExt: Iterator + Sized {
+    /// Pairs each item with a [`Loop`] describing its position.
+    ///
+    /// The iterator is peeked one item ahead, so that the last item can be
+    /// detected.
+    fn with_loop(self) -> WithLoop<Self> {
+        let len = match self.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        };
+        WithLoop {
+            iter: self.peekable(),
+            index: 0,
+            len,
+        }
+    }
+}
+
+impl<I: Iterator> LoopExt for I {}
+
+/// The iterator returned by [`LoopExt::with_loop`].
+pub struct WithLoop<I: Iterator> {
+    iter: Peekable<I>,
+    index: usize,
+    len: Option<usize>,
+}
+
+impl<I> Clone for WithLoop<I>
+where
+    I: Iterator + Clone,
+    I::Item: Clone,
+{
+    fn clone(&self) -> Self {
+        WithLoop {
+            iter: self.iter.clone(),
+            index: self.index,
+            len: self.len,
+        }
+    }
+}
+
+impl<I> fmt::Debug for WithLoop<I>
+where
+    I: Iterator + fmt::Debug,
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithLoop")
+            .field("iter", &self.iter)
+            .field("index", &self.index)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<I: Iterator> Iterator for WithLoop<I> {
+    type Item = (I::Item, Loop);
+
+    // `usize::is_multiple_of` needs a newer compiler than we support
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.next()?;
+        let index = self.index;
+        self.index += 1;
+        let meta = Loop {
+            index,
+            index1: index + 1,
+            first: index == 0,
+            last: self.iter.peek().is_none(),
+            even: index % 2 == 0,
+            odd: index % 2 == 1,
+            len: self.len,
+            revindex: self.len.map(|len| len.saturating_sub(index + 1)),
+        };
+        Some((item, meta))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for WithLoop<I> {}
+
+impl<I: FusedIterator> FusedIterator for WithLoop<I> {}
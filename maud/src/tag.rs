@@ -0,0 +1,183 @@
+use std::{borrow::Cow, fmt};
+
+/// A value that can be used as the name of an element with the
+/// `@tag(name)` syntax.
+///
+/// This is implemented for [`Tag`], which is always valid, and for strings,
+/// which are checked when the element is rendered.
+pub trait TagName {
+    /// Returns the name of the element, or `None` if it isn't valid.
+    ///
+    /// A valid name starts with an ASCII letter, and only contains ASCII
+    /// letters, digits, `-`, `_`, `.` and `:`. Elements with an invalid
+    /// name are rendered without their tags, so only their contents are
+    /// kept.
+    fn tag_name(&self) -> Option<&str>;
+}
+
+impl TagName for str {
+    fn tag_name(&self) -> Option<&str> {
+        if is_valid_name(self) {
+            Some(self)
+        } else {
+            None
+        }
+    }
+}
+
+impl TagName for String {
+    fn tag_name(&self) -> Option<&str> {
+        str::tag_name(self)
+    }
+}
+
+impl TagName for Cow<'_, str> {
+    fn tag_name(&self) -> Option<&str> {
+        str::tag_name(self)
+    }
+}
+
+impl<T: TagName + ?Sized> TagName for &T {
+    fn tag_name(&self) -> Option<&str> {
+        T::tag_name(self)
+    }
+}
+
+impl TagName for Tag {
+    fn tag_name(&self) -> Option<&str> {
+        Some(self.as_str())
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+macro_rules! tags {
+    ($($variant:ident => $name:literal,)*) => {
+        /// A standard HTML element.
+        ///
+        /// This can be used with the `@tag(name)` syntax to choose an element
+        /// at runtime.
+        ///
+        /// # Example
+        ///
+        /// ```rust
+        /// use maud::{html, Tag};
+        ///
+        /// let level = 2;
+        /// let markup = html! {
+        ///     @tag(Tag::heading(level)) .title { "Hello" }
+        /// };
+        /// assert_eq!(markup.into_string(), r#"<h2 class="title">Hello</h2>"#);
+        /// ```
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Tag {
+            $(
+                #[doc = concat!("The `<", $name, ">` element.")]
+                $variant,
+            )*
+        }
+
+        impl Tag {
+            /// Returns the name of the element.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Tag::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+tags! {
+    A => "a",
+    Abbr => "abbr",
+    Address => "address",
+    Article => "article",
+    Aside => "aside",
+    B => "b",
+    Blockquote => "blockquote",
+    Button => "button",
+    Caption => "caption",
+    Cite => "cite",
+    Code => "code",
+    Dd => "dd",
+    Del => "del",
+    Details => "details",
+    Dfn => "dfn",
+    Dialog => "dialog",
+    Div => "div",
+    Dl => "dl",
+    Dt => "dt",
+    Em => "em",
+    Fieldset => "fieldset",
+    Figcaption => "figcaption",
+    Figure => "figure",
+    Footer => "footer",
+    Form => "form",
+    H1 => "h1",
+    H2 => "h2",
+    H3 => "h3",
+    H4 => "h4",
+    H5 => "h5",
+    H6 => "h6",
+    Header => "header",
+    I => "i",
+    Ins => "ins",
+    Kbd => "kbd",
+    Label => "label",
+    Legend => "legend",
+    Li => "li",
+    Main => "main",
+    Mark => "mark",
+    Nav => "nav",
+    Ol => "ol",
+    P => "p",
+    Pre => "pre",
+    Q => "q",
+    S => "s",
+    Samp => "samp",
+    Section => "section",
+    Small => "small",
+    Span => "span",
+    Strong => "strong",
+    Sub => "sub",
+    Summary => "summary",
+    Sup => "sup",
+    Table => "table",
+    Tbody => "tbody",
+    Td => "td",
+    Tfoot => "tfoot",
+    Th => "th",
+    Thead => "thead",
+    Time => "time",
+    Tr => "tr",
+    U => "u",
+    Ul => "ul",
+    Var => "var",
+}
+
+impl Tag {
+    /// Returns the heading element for the given level, from `h1` to `h6`.
+    ///
+    /// Levels outside of that range are clamped.
+    pub fn heading(level: u8) -> Tag {
+        match level {
+            0 | 1 => Tag::H1,
+            2 => Tag::H2,
+            3 => Tag::H3,
+            4 => Tag::H4,
+            5 => Tag::H5,
+            _ => Tag::H6,
+        }
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,105 @@
+//! Integration with [zephyr](https://github.com/annieversary/zephyr).
+//!
+//! Everything from the `zephyr` crate is re-exported here. With the
+//! `zephyr_build` feature, this also has the helpers for generating CSS
+//! from the classes that `html!` records while compiling.
+
+pub use ::zephyr::*;
+
+#[cfg(feature = "zephyr_build")]
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Returns the directory that `html!` and `class!` record classes in.
+///
+/// This is `MAUD_ZEPHYR_DIR`, as it was set while compiling. It has to be
+/// an absolute path, since the compiler runs in a different directory for
+/// each dependency. Cargo can make it absolute for you:
+///
+/// ```toml
+/// # .cargo/config.toml
+/// [env]
+/// MAUD_ZEPHYR_DIR = { value = "target/maud-zephyr", relative = true }
+/// ```
+///
+/// Returns an error if it wasn't set, or wasn't absolute.
+#[cfg(feature = "zephyr_build")]
+pub fn classes_dir() -> io::Result<PathBuf> {
+    maud_macros::zephyr_classes_dir!()
+        .map(PathBuf::from)
+        .map_err(|message| io::Error::new(io::ErrorKind::NotFound, message))
+}
+
+/// Reads the classes recorded in `dir`, without duplicates.
+///
+/// A missing directory is treated as empty, since it just means that no
+/// classes have been recorded yet.
+#[cfg(feature = "zephyr_build")]
+pub fn recorded_classes(dir: impl AsRef<Path>) -> io::Result<Vec<String>> {
+    let mut classes = Vec::new();
+    let crate_dirs = match fs::read_dir(dir) {
+        Ok(crate_dirs) => crate_dirs,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(classes),
+        Err(error) => return Err(error),
+    };
+    for crate_dir in crate_dirs {
+        for file in fs::read_dir(crate_dir?.path())? {
+            let contents = fs::read_to_string(file?.path())?;
+            classes.extend(contents.split_whitespace().map(str::to_owned));
+        }
+    }
+    classes.sort_unstable();
+    classes.dedup();
+    Ok(classes)
+}
+
+/// Generates CSS for the classes recorded in [`classes_dir`], and writes
+/// it to `css_path`.
+///
+/// This is meant to be called from a small binary or a build script, once
+/// the crates with templates have been compiled.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// fn main() -> std::io::Result<()> {
+///     maud::zephyr::build("static/style.css")
+/// }
+/// ```
+#[cfg(feature = "zephyr_build")]
+pub fn build(css_path: impl AsRef<Path>) -> io::Result<()> {
+    let classes = recorded_classes(classes_dir()?)?;
+    let css = Zephyr::new().generate_classes(classes.iter().map(String::as_str));
+    fs::write(css_path, css)
+}
+
+#[cfg(all(test, feature = "zephyr_build"))]
+mod test {
+    use std::{env, fs, process};
+
+    use super::recorded_classes;
+
+    #[test]
+    fn recorded_classes_missing_dir() {
+        let dir = env::temp_dir().join(format!("maud-zephyr-missing-{}", process::id()));
+        assert_eq!(recorded_classes(dir).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn recorded_classes_merged() {
+        let dir = env::temp_dir().join(format!("maud-zephyr-test-{}", process::id()));
+        fs::create_dir_all(dir.join("app")).unwrap();
+        fs::create_dir_all(dir.join("ui")).unwrap();
+        fs::write(dir.join("app/app.txt"), "p-2\nflex\ntext-red").unwrap();
+        fs::write(dir.join("app/bin-app.txt"), "flex\nm-1").unwrap();
+        fs::write(dir.join("ui/ui.txt"), "text-red  hover:underline\n").unwrap();
+        let classes = recorded_classes(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            classes.unwrap(),
+            ["flex", "hover:underline", "m-1", "p-2", "text-red"],
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use maud::{html, Attributes};
+use std::collections::BTreeMap;
+
+#[test]
+fn spread_pairs() {
+    let attrs = [("data-a", "1"), ("title", "hi")];
+    let result = html! { div ..(attrs) {} };
+    assert_eq!(result.into_string(), r#"<div data-a="1" title="hi"></div>"#);
+}
+
+#[test]
+fn spread_map() {
+    let mut attrs = BTreeMap::new();
+    attrs.insert("name", "email".to_string());
+    attrs.insert("type", "email".to_string());
+    let result = html! { input ..(&attrs); };
+    assert_eq!(result.into_string(), r#"<input name="email" type="email">"#);
+}
+
+#[test]
+fn spread_attributes() {
+    let mut attrs = Attributes::new();
+    attrs.insert("data-count", 3);
+    attrs.insert("aria-label", "Close");
+    let result = html! { button ..(&attrs) { "x" } };
+    assert_eq!(
+        result.into_string(),
+        r#"<button data-count="3" aria-label="Close">x</button>"#
+    );
+}
+
+#[test]
+fn spread_escapes_values() {
+    let attrs = [("title", r#""><script>"#)];
+    let result = html! { p ..(attrs) {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<p title="&quot;&gt;&lt;script&gt;"></p>"#
+    );
+}
+
+#[test]
+fn spread_skips_invalid_names() {
+    let attrs = [
+        ("onclick=\"alert(1)\"", "x"),
+        ("a b", "x"),
+        ("", "x"),
+        ("data-ok", "y"),
+    ];
+    let result = html! { p ..(attrs) {} };
+    assert_eq!(result.into_string(), r#"<p data-ok="y"></p>"#);
+}
+
+#[test]
+fn spread_merges_class_and_style() {
+    let attrs = [("class", "big"), ("style", "margin: 0"), ("id", "spread")];
+    let result = html! {
+        div.card #main style="color: red" ..(attrs) {}
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="card big" id="main" style="color: red;margin: 0"></div>"#
+    );
+}
+
+#[test]
+fn spread_class_without_static_class() {
+    let attrs = [("class", "big")];
+    let result = html! { div ..(attrs) {} };
+    assert_eq!(result.into_string(), r#"<div class="big"></div>"#);
+}
+
+#[test]
+fn multiple_spreads() {
+    let a = [("class", "a"), ("title", "first")];
+    let b = [("class", "b"), ("title", "second")];
+    let result = html! { div ..(a) ..(b) {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="a b" title="second"></div>"#
+    );
+}
+
+#[test]
+fn attributes_insert() {
+    let mut attrs: Attributes = [("class", "a"), ("title", "x")].into_iter().collect();
+    attrs.insert("CLASS", "b");
+    assert_eq!(attrs.get("class"), Some("a b"));
+    attrs.insert("title", "<y>");
+    assert_eq!(attrs.get("title"), Some("&lt;y&gt;"));
+    assert_eq!(attrs.len(), 2);
+    assert_eq!(attrs.remove("title"), Some("&lt;y&gt;".to_string()));
+    assert!(!attrs.contains("title"));
+}
+
+#[test]
+fn spread_attributes_into_attributes() {
+    let mut inner = Attributes::new();
+    inner.insert("title", "a&b");
+    let outer: Attributes = inner.iter().collect();
+    let result = html! { p ..(outer) {} };
+    assert_eq!(result.into_string(), r#"<p title="a&amp;b"></p>"#);
+}
+
+#[test]
+fn class_shorthand_and_attribute() {
+    let extra = "wide";
+    let result = html! { div.card class=(extra) {} };
+    assert_eq!(result.into_string(), r#"<div class="card wide"></div>"#);
+}
+
+#[test]
+fn class_attribute_before_shorthand() {
+    let result = html! { div class="first" title="t" .second {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="second first" title="t"></div>"#
+    );
+}
+
+#[test]
+fn class_toggles_and_attributes() {
+    fn test(is_open: bool, extra: Option<&str>) -> String {
+        html! { div.card.open[is_open] class="a" class=[extra] {} }.into_string()
+    }
+    assert_eq!(
+        test(true, Some("b")),
+        r#"<div class="card open a b"></div>"#
+    );
+    assert_eq!(test(false, None), r#"<div class="card a"></div>"#);
+}
+
+#[test]
+fn class_attribute_with_spread() {
+    let extra = [("class", "spread")];
+    let result = html! { div.card class="own" ..(extra) {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="card own spread"></div>"#
+    );
+}
+
+#[test]
+fn style_attributes() {
+    let color = Some("color: red");
+    let result = html! { p style="margin: 0" style=[color] style="padding: 0" {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<p style="margin: 0;color: red;padding: 0"></p>"#
+    );
+}
+
+#[test]
+fn style_attributes_trailing_semicolon() {
+    let color = Some("color: red;");
+    assert_eq!(
+        html! { p style="margin: 0;" style="padding: 0" {} }.into_string(),
+        r#"<p style="margin: 0;padding: 0"></p>"#
+    );
+    assert_eq!(
+        html! { p style=[color] style="padding: 0" {} }.into_string(),
+        r#"<p style="color: red;padding: 0"></p>"#
+    );
+}
+
+#[test]
+fn merged_attributes_absent_parts() {
+    let none = None::<&str>;
+    let open = false;
+    assert_eq!(
+        html! { div class=[none] class="a" {} }.into_string(),
+        r#"<div class="a"></div>"#
+    );
+    assert_eq!(
+        html! { div.open[open] class="a" {} }.into_string(),
+        r#"<div class="a"></div>"#
+    );
+    assert_eq!(
+        html! { div style=[none] style="color: red" {} }.into_string(),
+        r#"<div style="color: red"></div>"#
+    );
+    assert_eq!(
+        html! { div.open[open] class=[none] style=[none] style=[none] {} }.into_string(),
+        "<div></div>"
+    );
+}
+
+#[test]
+fn merged_attributes_absent_parts_with_spread() {
+    let none = None::<&str>;
+    let extra = [("class", "spread")];
+    assert_eq!(
+        html! { div class=[none] ..(extra) {} }.into_string(),
+        r#"<div class="spread"></div>"#
+    );
+    let empty: [(&str, &str); 0] = [];
+    assert_eq!(
+        html! { div.x[false] ..(empty) {} }.into_string(),
+        "<div></div>"
+    );
+}
+
+#[test]
+fn merged_attributes_absent_parts_on_component() {
+    fn button(attrs: Attributes) -> maud::Markup {
+        html! { button ..(attrs) {} }
+    }
+    let none = None::<&str>;
+    let result = html! { @button() class=[none] class="a" .b[false]; };
+    assert_eq!(result.into_string(), r#"<button class="a"></button>"#);
+}
+
+#[test]
+fn class_on_component() {
+    fn button(attrs: Attributes) -> maud::Markup {
+        html! { button ..(attrs) {} }
+    }
+    let result = html! { @button().primary class="large"; };
+    assert_eq!(
+        result.into_string(),
+        r#"<button class="primary large"></button>"#
+    );
+}
+
+#[test]
+fn dynamic_attribute_name() {
+    let key = "user-id";
+    let result = html! { div (format!("data-{key}"))="42" {} };
+    assert_eq!(result.into_string(), r#"<div data-user-id="42"></div>"#);
+}
+
+#[test]
+fn dynamic_attribute_name_parts() {
+    let verb = "post";
+    let url = "/save?a=1&b=2";
+    let result = html! { button hx-(verb)=(url) hx-target="#out" { "Save" } };
+    assert_eq!(
+        result.into_string(),
+        r##"<button hx-post="/save?a=1&amp;b=2" hx-target="#out">Save</button>"##
+    );
+}
+
+#[test]
+fn dynamic_attribute_name_with_spread() {
+    let verb = "get";
+    let extra = [("hx-get", "/old"), ("hx-swap", "none")];
+    let result = html! { div hx-(verb)="/new" ..(extra) id="x" {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div hx-get="/new" id="x" hx-swap="none"></div>"#
+    );
+}
+
+#[test]
+fn dynamic_attribute_name_prefix() {
+    let prefix = "aria";
+    let result = html! { span (prefix)-hidden="true" {} };
+    assert_eq!(result.into_string(), r#"<span aria-hidden="true"></span>"#);
+}
+
+#[test]
+fn dynamic_attribute_name_toggles() {
+    let name = "disabled";
+    let title: Option<&str> = None;
+    let result = html! { input (name)[true] (format!("ti{}", "tle"))=[title]; };
+    assert_eq!(result.into_string(), "<input disabled>");
+}
+
+#[test]
+fn dynamic_attribute_name_invalid() {
+    let name = r#"x" onclick="alert(1)"#;
+    let result = html! { p (name)="y" data-(" a")="z" {} };
+    assert_eq!(result.into_string(), "<p></p>");
+}
+
+#[test]
+fn dynamic_attribute_name_on_component() {
+    fn link(attrs: Attributes) -> maud::Markup {
+        html! { span ..(attrs) {} }
+    }
+    let verb = "get";
+    let result = html! { @link() hx-(verb)="/x"; };
+    assert_eq!(result.into_string(), r#"<span hx-get="/x"></span>"#);
+}
+
+#[test]
+fn valid_attribute_names() {
+    assert!(Attributes::is_valid_name("data-x"));
+    assert!(Attributes::is_valid_name("xml:lang"));
+    assert!(Attributes::is_valid_name("@click"));
+    assert!(!Attributes::is_valid_name(""));
+    assert!(!Attributes::is_valid_name("a b"));
+    assert!(!Attributes::is_valid_name("a=b"));
+    assert!(!Attributes::is_valid_name("a>"));
+    assert!(!Attributes::is_valid_name("'a'"));
+}
+
+#[test]
+fn boolean_attributes() {
+    fn test(is_checked: bool) -> String {
+        html! { input type="checkbox" checked=(is_checked) disabled=(!is_checked); }.into_string()
+    }
+    assert_eq!(test(true), r#"<input type="checkbox" checked>"#);
+    assert_eq!(test(false), r#"<input type="checkbox" disabled>"#);
+}
+
+#[test]
+fn boolean_attributes_by_reference() {
+    let flags = [true, false];
+    let result = html! {
+        @for flag in &flags {
+            option selected=(flag) {}
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<option selected></option><option></option>"
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn boolean_attributes_with_value() {
+    let result = html! { details open=("yes") {} };
+    assert_eq!(result.into_string(), r#"<details open="yes"></details>"#);
+}
+
+#[test]
+fn boolean_attributes_on_component() {
+    fn field(attrs: Attributes) -> maud::Markup {
+        html! { input ..(attrs); }
+    }
+    let result = html! {
+        @field() required=(true) readonly=(false);
+    };
+    assert_eq!(result.into_string(), r#"<input required="">"#);
+}
+
+#[test]
+fn attribute_if() {
+    let link = |url: Option<&str>| {
+        html! {
+            button @if let Some(url) = url { formaction=(url) formtarget="_blank" } @else { disabled } {
+                "Go"
+            }
+        }
+        .into_string()
+    };
+    assert_eq!(
+        link(Some("/go")),
+        r#"<button formaction="/go" formtarget="_blank">Go</button>"#
+    );
+    assert_eq!(link(None), "<button disabled>Go</button>");
+}
+
+#[test]
+fn attribute_else_if() {
+    let input = |n: u32| {
+        html! {
+            input.field @if n == 0 { type="hidden" } @else if n == 1 { type="text" autofocus };
+        }
+        .into_string()
+    };
+    assert_eq!(input(0), r#"<input class="field" type="hidden">"#);
+    assert_eq!(input(1), r#"<input class="field" type="text" autofocus>"#);
+    assert_eq!(input(2), r#"<input class="field">"#);
+}
+
+#[test]
+fn attribute_match() {
+    enum Size {
+        Small,
+        Large(u32),
+    }
+    let img = |size: Size| {
+        html! {
+            img src="cat.png" @match size {
+                Size::Small => { width="16" height="16" }
+                Size::Large(n) => { width=(n) .large },
+            };
+        }
+        .into_string()
+    };
+    assert_eq!(
+        img(Size::Small),
+        r#"<img src="cat.png" width="16" height="16">"#
+    );
+    assert_eq!(
+        img(Size::Large(64)),
+        r#"<img src="cat.png" class="large" width="64">"#
+    );
+}
+
+#[test]
+fn attribute_if_with_spread() {
+    let extra = [("title", "spread"), ("lang", "en")];
+    let result = html! {
+        p ..(extra) @if true { title="branch" } { "Hi" }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<p title="branch" lang="en">Hi</p>"#
+    );
+}
+
+#[test]
+fn attribute_if_not_taken_with_spread() {
+    let extra = [("title", "spread")];
+    let result = html! {
+        div @if false { title="branch" } ..(extra) {}
+    };
+    assert_eq!(result.into_string(), r#"<div title="spread"></div>"#);
+}
+
+#[test]
+fn attribute_if_spread_in_branch() {
+    let outer = [("lang", "en"), ("class", "outer")];
+    let inner = [("title", "inner"), ("class", "inner")];
+    let result = html! {
+        div ..(outer) @if true { class="own" ..(inner) } {}
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="own outer inner" lang="en" title="inner"></div>"#
+    );
+}
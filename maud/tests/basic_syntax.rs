@@ -337,3 +337,358 @@ fn div_shorthand_id_with_attrs() {
         r#"<div class="awesome-class" id="unique-id" contenteditable dir="rtl"></div>"#
     );
 }
+
+#[test]
+fn toggle_id() {
+    fn test(is_active: bool) -> Markup {
+        html!(p #active[is_active] { "Testing!" })
+    }
+    assert_eq!(test(true).into_string(), r#"<p id="active">Testing!</p>"#);
+    assert_eq!(test(false).into_string(), r#"<p>Testing!</p>"#);
+}
+
+#[test]
+fn toggle_id_splice() {
+    let slug = "intro";
+    let result = html! { h2 #(slug)[true] { "Hi" } };
+    assert_eq!(result.into_string(), r#"<h2 id="intro">Hi</h2>"#);
+}
+
+#[test]
+fn optional_id() {
+    fn test(id: Option<&str>) -> Markup {
+        html!(p.note #[id] { "Testing!" })
+    }
+    assert_eq!(
+        test(Some("a&b")).into_string(),
+        r#"<p class="note" id="a&amp;b">Testing!</p>"#
+    );
+    assert_eq!(test(None).into_string(), r#"<p class="note">Testing!</p>"#);
+}
+
+#[test]
+fn conditional_ids_with_fallback() {
+    fn test(id: Option<u32>, is_first: bool) -> Markup {
+        html!(li #first[is_first] #[id] #item { "Testing!" })
+    }
+    assert_eq!(
+        test(Some(3), true).into_string(),
+        r#"<li id="first">Testing!</li>"#
+    );
+    assert_eq!(
+        test(Some(3), false).into_string(),
+        r#"<li id="3">Testing!</li>"#
+    );
+    assert_eq!(
+        test(None, false).into_string(),
+        r#"<li id="item">Testing!</li>"#
+    );
+}
+
+#[test]
+fn optional_id_with_spread() {
+    fn test(id: Option<&str>) -> Markup {
+        html!(p #[id] ..([("id", "spread"), ("title", "t")]) {})
+    }
+    assert_eq!(
+        test(Some("own")).into_string(),
+        r#"<p id="own" title="t"></p>"#
+    );
+    assert_eq!(test(None).into_string(), r#"<p id="spread" title="t"></p>"#);
+}
+
+#[test]
+fn dynamic_element() {
+    let level = 3;
+    let result = html! {
+        @tag(maud::Tag::heading(level)) .title #intro { "Hello" }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<h3 class="title" id="intro">Hello</h3>"#
+    );
+}
+
+#[test]
+fn dynamic_element_string() {
+    fn link_or_button(href: Option<&str>) -> Markup {
+        html! {
+            @tag(if href.is_some() { "a" } else { "button" }) href=[href] {
+                "Go"
+            }
+        }
+    }
+    assert_eq!(
+        link_or_button(Some("/")).into_string(),
+        r#"<a href="/">Go</a>"#
+    );
+    assert_eq!(link_or_button(None).into_string(), "<button>Go</button>");
+}
+
+#[test]
+fn dynamic_element_void() {
+    let name = String::from("input");
+    let result = html! { @tag(name) type="text"; };
+    assert_eq!(result.into_string(), r#"<input type="text">"#);
+}
+
+#[test]
+fn dynamic_element_nested() {
+    let result = html! {
+        @tag("section") {
+            @tag(maud::Tag::Div) { @tag("span") { "x" } }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<section><div><span>x</span></div></section>"
+    );
+}
+
+#[test]
+fn dynamic_element_invalid_name() {
+    let name = "div onclick=alert(1)";
+    let result = html! { p { @tag(name) .x { "kept" } } @tag(name); };
+    assert_eq!(result.into_string(), "<p>kept</p>");
+}
+
+#[test]
+fn comment() {
+    let result = html! { p { @comment { " hello " } "world" } };
+    assert_eq!(result.into_string(), "<p><!-- hello -->world</p>");
+}
+
+#[test]
+fn comment_splices() {
+    let version = "1.2 <beta>";
+    let result = html! { @comment { " version: " (version) ", build " (42) " " } };
+    assert_eq!(
+        result.into_string(),
+        "<!-- version: 1.2 &lt;beta&gt;, build 42 -->"
+    );
+}
+
+#[test]
+fn comment_escapes_dashes() {
+    let evil = maud::PreEscaped("--><script>alert(1)</script><!--");
+    let result = html! { @comment { " " (evil) " " (" a-b- ") "-" } };
+    assert_eq!(
+        result.into_string(),
+        "<!-- &#45;&#45;&gt;&lt;script&gt;alert(1)&lt;/script&gt;&lt;!-&#45;  a-b- --->"
+    );
+}
+
+#[test]
+fn comment_component_name() {
+    fn comment(text: &str) -> Markup {
+        html! { "(" (text) ")" }
+    }
+    let result = html! { @comment("hi"); };
+    assert_eq!(result.into_string(), "(hi)");
+}
+
+#[test]
+fn raw() {
+    let result = html! {
+        div {
+            @raw { "<p class=\"a\">Hello <b>world</b><br></p>" }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<div><p class="a">Hello <b>world</b><br></p></div>"#
+    );
+}
+
+#[test]
+fn raw_multiple_literals() {
+    let result = html! {
+        @raw {
+            r#"<script async src="https://example.com/a.js"></script>"#
+            "<script>if (a < b && c > d) { go(); }</script>"
+            "<!-- <unclosed> --><img src='x.png' alt=\"a > b\"/>"
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        concat!(
+            r#"<script async src="https://example.com/a.js"></script>"#,
+            "<script>if (a < b && c > d) { go(); }</script>",
+            "<!-- <unclosed> --><img src='x.png' alt=\"a > b\"/>",
+        )
+    );
+}
+
+#[test]
+fn entities() {
+    let result = html! { p { "Fish " &amp; " chips" &nbsp; &mdash; &frac12; &in; } };
+    assert_eq!(
+        result.into_string(),
+        "<p>Fish &amp; chips&nbsp;&mdash;&frac12;&in;</p>"
+    );
+}
+
+#[test]
+fn numeric_entities() {
+    let result = html! { &#169; &#x2014; &#X1F600; };
+    assert_eq!(result.into_string(), "&#169;&#x2014;&#X1F600;");
+}
+
+#[test]
+fn entities_in_attributes() {
+    let result = html! { abbr title={ "Tom " &amp; " Jerry" } { "T&J" } };
+    assert_eq!(
+        result.into_string(),
+        r#"<abbr title="Tom &amp; Jerry">T&amp;J</abbr>"#
+    );
+}
+
+#[test]
+fn text_dedent() {
+    let result = html! {
+        p {
+            @text { r#"
+                Roses are red,
+                  violets are blue.
+            "# }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<p>Roses are red,\n  violets are blue.</p>"
+    );
+}
+
+#[test]
+fn text_reflow() {
+    let result = html! {
+        @text { reflow r#"
+            The quick brown fox
+            jumps over the lazy dog.
+
+            Pack my box with
+            five dozen <liquor> jugs.
+        "# }
+    };
+    assert_eq!(
+        result.into_string(),
+        "The quick brown fox jumps over the lazy dog.\n\nPack my box with five dozen &lt;liquor&gt; jugs."
+    );
+}
+
+#[test]
+fn text_in_pre() {
+    let result = html! {
+        pre {
+            @text { reflow "
+    fn main() {}
+" }
+        }
+        textarea { @text { "  a\n  b" } }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<pre>\n    fn main() {}\n</pre><textarea>  a\n  b</textarea>"
+    );
+}
+
+#[test]
+fn text_component() {
+    fn text(value: &str) -> maud::Markup {
+        html! { span { (value) } }
+    }
+    let result = html! { @text("hi"); };
+    assert_eq!(result.into_string(), "<span>hi</span>");
+}
+
+#[test]
+fn whitespace_tokens() {
+    let result = html! { b { "bold" } @sp i { "italic" } @nl span { "next" } };
+    assert_eq!(
+        result.into_string(),
+        "<b>bold</b> <i>italic</i>\n<span>next</span>"
+    );
+}
+
+#[test]
+fn utility_classes() {
+    let result = html! { div.{m[1rem] hover:bg-red w-1/2} {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="m[1rem] hover:bg-red w-1/2"></div>"#
+    );
+}
+
+#[test]
+fn utility_classes_mixed() {
+    let result = html! {
+        p.card.{ p-2   w-[50%] }.big.{-mt-4} #main { "Hi" }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<p class="card p-2 w-[50%] big -mt-4" id="main">Hi</p>"#
+    );
+}
+
+#[test]
+fn utility_classes_with_comments() {
+    let result = html! {
+        div.{
+            m-1 // note
+            p-2 /* x */ w-[50%]
+        } {}
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="m-1 p-2 w-[50%]"></div>"#
+    );
+}
+
+#[test]
+fn utility_classes_quoted() {
+    let result = html! { div.{m-1 "-mt-2" p-2.5 "!font-bold"} {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="m-1 -mt-2 p-2.5 !font-bold"></div>"#
+    );
+}
+
+#[test]
+fn utility_classes_from_macro() {
+    macro_rules! card {
+        ($($class:tt)*) => {
+            html! { div.{ card $($class)* } {} }
+        };
+    }
+    assert_eq!(
+        card!(p-2 hover:bg-red).into_string(),
+        r#"<div class="card p-2 hover:bg-red"></div>"#
+    );
+}
+
+#[test]
+fn variant_groups() {
+    let result = html! { p.card.hover:(text-red bg-blue underline) { "Hi" } };
+    assert_eq!(
+        result.into_string(),
+        r#"<p class="card hover:text-red hover:bg-blue hover:underline">Hi</p>"#
+    );
+}
+
+#[test]
+fn variant_groups_chained_prefix() {
+    let result = html! { div.md:hover:(a b).c {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="md:hover:a md:hover:b c"></div>"#
+    );
+}
+
+#[test]
+fn variant_groups_in_utility_classes() {
+    let result = html! { div.{p-2 md:(m[1rem] hover:(underline font-bold)) w-1/2} {} };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="p-2 md:m[1rem] md:hover:underline md:hover:font-bold w-1/2"></div>"#
+    );
+}
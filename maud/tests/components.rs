@@ -0,0 +1,452 @@
+use maud::{html, Attributes, Children, Markup, Render};
+
+fn title(text: &str) -> Markup {
+    html! { h1 { (text) } }
+}
+
+fn card(title: &str, children: Markup) -> Markup {
+    html! {
+        div.card {
+            h2 { (title) }
+            (children)
+        }
+    }
+}
+
+fn layout(title: &str, children: Children) -> Markup {
+    html! {
+        main {
+            h1 { (title) }
+            (children)
+        }
+    }
+}
+
+#[test]
+fn component_without_body() {
+    let result = html! { @title("hello"); };
+    assert_eq!(result.into_string(), "<h1>hello</h1>");
+}
+
+#[test]
+fn component_with_markup_body() {
+    let result = html! {
+        @card("pinkie") {
+            p { "pie" }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="card"><h2>pinkie</h2><p>pie</p></div>"#
+    );
+}
+
+#[test]
+fn component_with_lazy_body() {
+    let name = "Rarity";
+    let result = html! {
+        @layout("Hello") || {
+            p { "Hi, " (name) "!" }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<main><h1>Hello</h1><p>Hi, Rarity!</p></main>"
+    );
+}
+
+#[test]
+fn nested_lazy_bodies() {
+    let result = html! {
+        @layout("outer") || {
+            @layout("inner") || {
+                @card("card") { "body" }
+            }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        concat!(
+            "<main><h1>outer</h1>",
+            "<main><h1>inner</h1>",
+            r#"<div class="card"><h2>card</h2>body</div>"#,
+            "</main></main>"
+        )
+    );
+}
+
+#[test]
+fn body_rendered_twice() {
+    fn twice(children: Markup) -> Markup {
+        html! { (children) (children) }
+    }
+    let result = html! { @twice() { "ha" } };
+    assert_eq!(result.into_string(), "haha");
+}
+
+#[test]
+fn body_moves_captured_values() {
+    let items = vec!["a".to_string(), "b".to_string()];
+    let result = html! {
+        @card("list") {
+            @for item in items {
+                span { (item) }
+            }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<div class="card"><h2>list</h2><span>a</span><span>b</span></div>"#
+    );
+}
+
+#[test]
+fn lazy_body_moves_captured_values() {
+    let items = vec!["a".to_string(), "b".to_string()];
+    let result = html! {
+        @layout("list") || {
+            @for item in items {
+                span { (item) }
+            }
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<main><h1>list</h1><span>a</span><span>b</span></main>"
+    );
+}
+
+#[test]
+fn lazy_body_as_impl_render() {
+    fn wrap(children: impl Render) -> Markup {
+        html! { div { (children) } }
+    }
+    let result = html! { @wrap() || { "inside" } };
+    assert_eq!(result.into_string(), "<div>inside</div>");
+}
+
+#[test]
+fn lazy_body_runs_when_spliced() {
+    use std::cell::Cell;
+
+    fn opened(flag: &Cell<bool>, children: Children) -> Markup {
+        flag.set(true);
+        html! { (children) }
+    }
+    let flag = Cell::new(false);
+    let result = html! {
+        @opened(&flag) || {
+            @if flag.get() { "lazy" } @else { "eager" }
+        }
+    };
+    assert_eq!(result.into_string(), "lazy");
+}
+
+#[test]
+#[should_panic(expected = "can only be rendered once")]
+fn lazy_body_rendered_twice() {
+    fn twice(children: Children) -> Markup {
+        html! { (children) (children) }
+    }
+    let _ = html! { @twice() || { "ha" } };
+}
+
+#[test]
+fn body_as_impl_render() {
+    fn wrap(children: impl Render) -> Markup {
+        html! { div { (children) } }
+    }
+    let result = html! { @wrap() { "inside" } };
+    assert_eq!(result.into_string(), "<div>inside</div>");
+}
+
+mod ui {
+    use maud::{html, Markup};
+
+    pub fn button(label: &str) -> Markup {
+        html! { button { (label) } }
+    }
+
+    pub fn list<T: std::fmt::Display>(items: &[T]) -> Markup {
+        html! {
+            ul {
+                @for item in items {
+                    li { (item.to_string()) }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn component_with_path() {
+    let result = html! { @ui::button("ok"); };
+    assert_eq!(result.into_string(), "<button>ok</button>");
+}
+
+#[test]
+fn component_with_absolute_path() {
+    let result = html! { @crate::ui::button("ok"); };
+    assert_eq!(result.into_string(), "<button>ok</button>");
+}
+
+#[test]
+fn component_with_turbofish() {
+    let result = html! { @ui::list::<u32>(&[1, 2]); };
+    assert_eq!(result.into_string(), "<ul><li>1</li><li>2</li></ul>");
+}
+
+#[test]
+fn component_with_nested_generics() {
+    fn count<T>(items: Vec<Vec<T>>) -> usize {
+        items.len()
+    }
+    let result = html! { @count::<Vec<u8>>(vec![]); };
+    assert_eq!(result.into_string(), "0");
+}
+
+#[test]
+fn component_methods() {
+    struct Page {
+        title: &'static str,
+    }
+
+    impl Page {
+        fn header(&self) -> Markup {
+            html! { h1 { (self.title) } }
+        }
+
+        fn row(label: &str, children: Markup) -> Markup {
+            html! { tr { th { (label) } td { (children) } } }
+        }
+
+        fn render(&self) -> Markup {
+            html! {
+                @self.header();
+                @Self::row("a") { "b" }
+            }
+        }
+    }
+
+    let page = Page { title: "hello" };
+    assert_eq!(
+        page.render().into_string(),
+        "<h1>hello</h1><tr><th>a</th><td>b</td></tr>"
+    );
+    let result = html! { @page.header(); };
+    assert_eq!(result.into_string(), "<h1>hello</h1>");
+}
+
+#[test]
+fn component_method_chain() {
+    let items = ["a", "b"];
+    let result = html! { @items.iter().count(); };
+    assert_eq!(result.into_string(), "2");
+}
+
+fn button(label: &str, attrs: Attributes) -> Markup {
+    html! {
+        button.btn ..(attrs) { (label) }
+    }
+}
+
+fn panel(attrs: Attributes, children: Markup) -> Markup {
+    html! {
+        section ..(attrs) { (children) }
+    }
+}
+
+#[test]
+fn component_attributes() {
+    let result = html! {
+        @button("Save").primary #save-btn data-x="1";
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<button class="btn primary" id="save-btn" data-x="1">Save</button>"#
+    );
+}
+
+#[test]
+fn component_attributes_empty() {
+    let result = html! {
+        @button("Cancel")..;
+        @panel().. { "content" }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<button class="btn">Cancel</button><section>content</section>"#
+    );
+}
+
+#[test]
+fn component_attributes_empty_and_spread() {
+    let extra = [("data-x", "1")];
+    let result = html! { @button("Go").. ..(extra); };
+    assert_eq!(
+        result.into_string(),
+        r#"<button class="btn" data-x="1">Go</button>"#
+    );
+}
+
+#[test]
+fn component_attributes_variant_group() {
+    let result = html! { @button("Go").hover:(text-red underline); };
+    assert_eq!(
+        result.into_string(),
+        r#"<button class="btn hover:text-red hover:underline">Go</button>"#
+    );
+}
+
+#[test]
+fn component_attributes_with_body() {
+    let open = true;
+    let title = Some("<hi>");
+    let result = html! {
+        @panel() .wide[open] title=[title] hidden[!open] {
+            "content"
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<section class="wide" title="&lt;hi&gt;">content</section>"#
+    );
+}
+
+#[test]
+fn component_attributes_optional_id() {
+    fn test(id: Option<&str>) -> String {
+        html! {
+            @button("Save") #[id] #fallback[id.is_none()];
+        }
+        .into_string()
+    }
+    assert_eq!(
+        test(Some("save")),
+        r#"<button class="btn" id="save">Save</button>"#
+    );
+    assert_eq!(
+        test(None),
+        r#"<button class="btn" id="fallback">Save</button>"#
+    );
+}
+
+#[test]
+fn component_attributes_spread() {
+    let extra = [("class", "big"), ("data-x", "2")];
+    let result = html! {
+        @button("Go") ..(extra) data-x="1";
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<button class="btn big" data-x="1">Go</button>"#
+    );
+}
+
+#[test]
+fn local_fn() {
+    let rows = [("Name", 1), ("Age", 2)];
+    let result = html! {
+        @fn row(label: &str, value: impl Render) {
+            tr { th { (label) } td { (value) } }
+        }
+        table {
+            @for (label, value) in rows {
+                @row(label, value);
+            }
+            @row("Total", "3");
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        concat!(
+            "<table>",
+            "<tr><th>Name</th><td>1</td></tr>",
+            "<tr><th>Age</th><td>2</td></tr>",
+            "<tr><th>Total</th><td>3</td></tr>",
+            "</table>",
+        )
+    );
+}
+
+#[test]
+fn local_fn_children() {
+    let result = html! {
+        @fn panel(title: &str, children: Markup) {
+            section { h2 { (title) } (children) }
+        }
+        @fn rule() { hr; }
+        @panel("One") { p { "first" } }
+        @rule();
+        @panel("Two") {}
+    };
+    assert_eq!(
+        result.into_string(),
+        "<section><h2>One</h2><p>first</p></section><hr><section><h2>Two</h2></section>"
+    );
+}
+
+#[test]
+fn local_fn_lazy_children() {
+    let result = html! {
+        @fn panel(title: &str, children: Children) {
+            section { h2 { (title) } (children) }
+        }
+        @panel("One") || { p { "first" } }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<section><h2>One</h2><p>first</p></section>"
+    );
+}
+
+#[test]
+fn local_fn_scope() {
+    let result = html! {
+        div {
+            @fn item(n: u32) { span { (n) } }
+            @item(1);
+        }
+        div {
+            @fn item(n: u32) { b { (n) } }
+            @item(2);
+        }
+    };
+    assert_eq!(
+        result.into_string(),
+        "<div><span>1</span></div><div><b>2</b></div>"
+    );
+}
+
+#[test]
+fn component_attribute_if() {
+    let test = |primary: bool| {
+        html! {
+            @button("Go") @if primary { data-kind="primary" } @else { data-kind="plain" };
+        }
+        .into_string()
+    };
+    assert_eq!(
+        test(true),
+        r#"<button class="btn" data-kind="primary">Go</button>"#
+    );
+    assert_eq!(
+        test(false),
+        r#"<button class="btn" data-kind="plain">Go</button>"#
+    );
+}
+
+#[test]
+fn component_class_argument() {
+    fn badge(label: &str, class: maud::Class) -> Markup {
+        html! { span.badge class=(class) { (label) } }
+    }
+    let urgent = true;
+    let result = html! {
+        @badge("New", maud::Class(if urgent { "text-red" } else { "text-gray" }));
+    };
+    assert_eq!(
+        result.into_string(),
+        r#"<span class="badge text-red">New</span>"#
+    );
+}
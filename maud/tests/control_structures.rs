@@ -212,3 +212,199 @@ fn let_type_ascription() {
     };
     assert_eq!(result.into_string(), "I have 42 cupcakes!");
 }
+
+#[test]
+fn let_else() {
+    let values = [Some(1), None, Some(3)];
+    let result = html! {
+        @for value in values {
+            @let Some(n) = value else {
+                "none "
+                @{ continue; }
+            };
+            (n) " "
+        }
+    };
+    assert_eq!(result.into_string(), "1 none 3 ");
+}
+
+#[test]
+fn let_if_else() {
+    let flag = false;
+    let result = html! {
+        @let n = if flag { 1 } else { 2 };
+        (n)
+    };
+    assert_eq!(result.into_string(), "2");
+}
+
+#[test]
+fn let_closure_with_statements() {
+    let result = html! {
+        @let shout = |s: &str| { let s = s.to_uppercase(); s + "!" };
+        (shout("hi"))
+    };
+    assert_eq!(result.into_string(), "HI!");
+}
+
+#[test]
+fn statements() {
+    let result = html! {
+        @{
+            let mut items = vec![3, 1, 2];
+            items.sort();
+        }
+        @for n in &items { (n) }
+    };
+    assert_eq!(result.into_string(), "123");
+}
+
+#[test]
+fn statements_lexical_scope() {
+    let x = 1;
+    let result = html! {
+        {
+            @{ let x = 2; }
+            (x)
+        }
+        (x)
+    };
+    assert_eq!(result.into_string(), "21");
+}
+
+#[test]
+fn for_empty() {
+    fn list(items: &[&str]) -> String {
+        html! {
+            ul {
+                @for item in items {
+                    li { (item) }
+                } @empty {
+                    li.empty { "No results" }
+                }
+            }
+        }
+        .into_string()
+    }
+    assert_eq!(list(&["a", "b"]), "<ul><li>a</li><li>b</li></ul>");
+    assert_eq!(list(&[]), r#"<ul><li class="empty">No results</li></ul>"#);
+}
+
+#[test]
+fn for_separator() {
+    fn join(items: &[&str]) -> String {
+        html! {
+            @for item in items {
+                b { (item) }
+            } @separator {
+                ", "
+            }
+        }
+        .into_string()
+    }
+    assert_eq!(join(&["a", "b", "c"]), "<b>a</b>, <b>b</b>, <b>c</b>");
+    assert_eq!(join(&["a"]), "<b>a</b>");
+    assert_eq!(join(&[]), "");
+}
+
+#[test]
+fn for_separator_and_empty() {
+    fn numbers(range: std::ops::Range<u32>) -> String {
+        html! {
+            @for n in range.filter(|n| n % 2 == 0) {
+                (n)
+            } @empty {
+                "none"
+            } @separator {
+                " | "
+            }
+        }
+        .into_string()
+    }
+    assert_eq!(numbers(0..5), "0 | 2 | 4");
+    assert_eq!(numbers(1..2), "none");
+}
+
+#[test]
+fn for_nested_separators() {
+    let rows = [vec![1, 2], vec![], vec![3]];
+    let result = html! {
+        @for row in &rows {
+            @for n in row { (n) } @separator { "," } @empty { "-" }
+        } @separator { ";" }
+    };
+    assert_eq!(result.into_string(), "1,2;-;3");
+}
+
+#[test]
+fn for_followed_by_component() {
+    fn empty() -> maud::Markup {
+        html! { "!" }
+    }
+    let result = html! {
+        @for n in 0..2 { (n) }
+        @empty();
+    };
+    assert_eq!(result.into_string(), "01!");
+}
+
+#[test]
+fn for_loop_meta() {
+    let result = html! {
+        @for c in ['a', 'b', 'c'] with meta {
+            (meta.index) (meta.index1) (c)
+            @if meta.first { "F" }
+            @if meta.last { "L" }
+            @if meta.even { "e" }
+            @if meta.odd { "o" }
+            " "
+        }
+    };
+    assert_eq!(result.into_string(), "01aFe 12bo 23cLe ");
+}
+
+#[test]
+fn for_loop_meta_len() {
+    let result = html! {
+        @for n in vec![10, 20, 30] with meta {
+            (n) "/" (meta.len.unwrap()) "/" (meta.revindex.unwrap()) " "
+        }
+    };
+    assert_eq!(result.into_string(), "10/3/2 20/3/1 30/3/0 ");
+}
+
+#[test]
+fn for_loop_meta_unknown_len() {
+    let result = html! {
+        @for n in (1..10).filter(|n| n % 4 == 0) with meta {
+            (n)
+            @if meta.len.is_none() { "?" }
+            @if meta.last { "!" }
+        }
+    };
+    assert_eq!(result.into_string(), "4?8?!");
+}
+
+#[test]
+fn for_loop_meta_pattern() {
+    let pairs = [("a", 1), ("b", 2)];
+    let result = html! {
+        @for &(name, n) in &pairs with meta {
+            (name) "=" (n) @if !meta.last { ";" }
+        } @empty { "none" }
+    };
+    assert_eq!(result.into_string(), "a=1;b=2");
+}
+
+#[test]
+fn for_with_loop_adapter() {
+    use maud::LoopExt;
+    let result = html! {
+        @for (n, meta) in (0..3).with_loop() {
+            @if meta.first { "[" }
+            (n)
+            @if meta.last { "]" }
+        }
+    };
+    assert_eq!(result.into_string(), "[012]");
+}
@@ -83,3 +83,19 @@ fn issue_97() {
 
     assert_eq!(html! { (Pinkie) }.into_string(), "42");
 }
+
+#[test]
+fn class_macro() {
+    fn badge_class(urgent: bool) -> &'static str {
+        if urgent {
+            maud::class!("px-2 text-red")
+        } else {
+            maud::class!("px-2")
+        }
+    }
+    let result = html! { span class=(badge_class(true)) { "!" } };
+    assert_eq!(
+        result.into_string(),
+        r#"<span class="px-2 text-red">!</span>"#
+    );
+}
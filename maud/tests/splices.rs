@@ -124,3 +124,60 @@ fn nested_macro_invocation() {
     let result = html! { (format!("{} is best pony", best_pony)) };
     assert_eq!(result.into_string(), "Pinkie Pie is best pony");
 }
+
+#[test]
+fn format_string() {
+    let name = "<Pinkie>";
+    let n = 3;
+    let result = html! { p { @format { "Hello, {name}! You have {n} messages" } } };
+    assert_eq!(
+        result.into_string(),
+        "<p>Hello, &lt;Pinkie&gt;! You have 3 messages</p>"
+    );
+}
+
+#[test]
+fn format_string_fields_and_specs() {
+    struct User {
+        name: &'static str,
+        score: (f64, u32),
+    }
+    let user = User {
+        name: "Rarity",
+        score: (9.4567, 2),
+    };
+    let result = html! { @format { "{user.name}: {user.score.0:.2} ({ user.score.1 :>3})" } };
+    assert_eq!(result.into_string(), "Rarity: 9.46 (  2)");
+}
+
+#[test]
+fn format_string_escaped_braces() {
+    let x = 1;
+    let result = html! { @format { "{{x}} = {x} & <b>" } };
+    assert_eq!(result.into_string(), "{x} = 1 &amp; &lt;b&gt;");
+}
+
+#[test]
+fn format_string_in_attribute() {
+    let id = 42;
+    let result = html! { span title=@format { "item #{id}" } {} };
+    assert_eq!(result.into_string(), r#"<span title="item #42"></span>"#);
+}
+
+#[test]
+fn format_component() {
+    fn format(x: u32) -> maud::Markup {
+        html! { "#" (x) }
+    }
+    let result = html! { @format(21); };
+    assert_eq!(result.into_string(), "#21");
+}
+
+#[test]
+fn format_component_with_string() {
+    fn format(template: &str) -> maud::Markup {
+        html! { code { (template) } }
+    }
+    let result = html! { @format("{x}"); };
+    assert_eq!(result.into_string(), "<code>{x}</code>");
+}
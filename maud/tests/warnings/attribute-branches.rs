@@ -0,0 +1,14 @@
+use maud::html;
+
+fn main() {
+    let wide = true;
+    html! {
+        div title="a" @if wide { title="b" } {}
+    };
+    html! {
+        div.card @if wide { .wide } {}
+    };
+    html! {
+        div @if wide { "wide" } {}
+    };
+}
@@ -0,0 +1,10 @@
+#![deny(deprecated)]
+
+use maud::html;
+
+fn main() {
+    let is_checked = "false";
+    html! {
+        input type="checkbox" checked=(is_checked);
+    };
+}
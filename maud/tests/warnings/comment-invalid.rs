@@ -0,0 +1,10 @@
+use maud::html;
+
+fn main() {
+    html! {
+        @comment { "a -- b" }
+        @comment { p { "not text" } }
+        @comment { "a-" "-b" }
+        @comment { "a-" (String::new()) "-b" }
+    };
+}
@@ -0,0 +1,8 @@
+use maud::html;
+
+fn main() {
+    let name = "Applejack";
+    html! {
+        @format { "{name + 1} {" }
+    };
+}
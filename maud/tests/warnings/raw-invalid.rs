@@ -0,0 +1,10 @@
+use maud::html;
+
+fn main() {
+    html! {
+        @raw { "<div><span>oops</div>" }
+        @raw { "<p>" "</p>" "<em>" }
+        @raw { "<div class=\"x>" }
+        @raw { (1) }
+    };
+}
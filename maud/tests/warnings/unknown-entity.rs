@@ -0,0 +1,10 @@
+use maud::html;
+
+fn main() {
+    html! {
+        &nbps;
+        &NBSP;
+        &#xD800;
+        &mdash
+    };
+}
@@ -0,0 +1,8 @@
+use maud::html;
+
+fn main() {
+    let active = true;
+    html! {
+        div.{m[1rem] p-2}[active] {}
+    };
+}
@@ -1,4 +1,4 @@
-use proc_macro2::{TokenStream, TokenTree};
+use proc_macro2::{Ident, TokenStream, TokenTree};
 use proc_macro_error::SpanRange;
 
 #[derive(Debug)]
@@ -15,6 +15,11 @@ pub enum Markup {
     Symbol {
         symbol: TokenStream,
     },
+    /// A character reference like `&mdash;`, which has already been checked.
+    Entity {
+        entity: String,
+        span: SpanRange,
+    },
     Splice {
         expr: TokenStream,
         outer_span: SpanRange,
@@ -24,24 +29,85 @@ pub enum Markup {
         attrs: Vec<Attr>,
         body: ElementBody,
     },
+    /// An element whose name is only known at runtime, e.g. `@tag(name) {}`.
+    DynamicElement {
+        at_span: SpanRange,
+        expr: TokenStream,
+        attrs: Vec<Attr>,
+        body: ElementBody,
+    },
     Let {
         at_span: SpanRange,
         tokens: TokenStream,
+        /// The `else` branch of a `@let ... else { ... };`.
+        else_body: Option<Block>,
+    },
+    /// Rust statements from `@{ ... }`, emitted as is.
+    Statements {
+        at_span: SpanRange,
+        stmts: TokenStream,
+        stmts_span: SpanRange,
     },
     Special {
         segments: Vec<Special>,
     },
+    /// A component defined within the template, from `@fn name(...) { ... }`.
+    LocalFn {
+        at_span: SpanRange,
+        name: Ident,
+        params: TokenStream,
+        body: Block,
+    },
+    /// A call to a component defined with `@fn`.
+    LocalCall {
+        at_span: SpanRange,
+        name: Ident,
+        args: TokenStream,
+        body: ElementBody,
+        /// Whether the body is passed as `maud::Children`, see `Custom`.
+        lazy: bool,
+    },
+    For {
+        at_span: SpanRange,
+        pattern: TokenStream,
+        iter: TokenStream,
+        /// Bound to a `maud::Loop` on each iteration, from `with <ident>`.
+        meta: Option<Ident>,
+        body: Block,
+        /// Rendered between iterations.
+        separator: Option<Block>,
+        /// Rendered if the loop doesn't run at all.
+        empty: Option<Block>,
+    },
     Match {
         at_span: SpanRange,
         head: TokenStream,
         arms: Vec<MatchArm>,
         arms_span: SpanRange,
     },
+    /// Pre-written HTML from `@raw { ... }`, which has already been
+    /// checked.
+    Raw {
+        at_span: SpanRange,
+        content: String,
+        body_span: SpanRange,
+    },
+    /// An HTML comment from `@comment { ... }`, containing only literals
+    /// and splices.
+    Comment {
+        at_span: SpanRange,
+        body: Block,
+    },
     Custom {
         at_span: SpanRange,
         name: TokenStream,
         expr: TokenStream,
+        /// `None` if the component isn't passed any attributes.
+        attrs: Option<Vec<Attr>>,
         body: ElementBody,
+        /// Whether the body was written as a closure, e.g.
+        /// `@card() || { ... }`, and is passed as `maud::Children`.
+        lazy: bool,
     },
 }
 
@@ -52,6 +118,7 @@ impl Markup {
             Markup::Block(ref block) => block.span(),
             Markup::Literal { span, .. } => span,
             Markup::Symbol { ref symbol } => span_tokens(symbol.clone()),
+            Markup::Entity { span, .. } => span,
             Markup::Splice { outer_span, .. } => outer_span,
             Markup::Element {
                 ref name, ref body, ..
@@ -59,14 +126,41 @@ impl Markup {
                 let name_span = span_tokens(name.clone());
                 name_span.join_range(body.span())
             }
+            Markup::DynamicElement {
+                at_span, ref body, ..
+            } => at_span.join_range(body.span()),
             Markup::Let {
                 at_span,
                 ref tokens,
-            } => at_span.join_range(span_tokens(tokens.clone())),
+                ref else_body,
+            } => match else_body {
+                Some(else_body) => at_span.join_range(else_body.span()),
+                None => at_span.join_range(span_tokens(tokens.clone())),
+            },
+            Markup::Statements {
+                at_span,
+                stmts_span,
+                ..
+            } => at_span.join_range(stmts_span),
             Markup::Special { ref segments } => join_ranges(segments.iter().map(Special::span)),
+            Markup::LocalFn {
+                at_span, ref body, ..
+            } => at_span.join_range(body.span()),
+            Markup::LocalCall {
+                at_span, ref body, ..
+            } => at_span.join_range(body.span()),
+            Markup::For {
+                at_span, ref body, ..
+            } => at_span.join_range(body.span()),
             Markup::Match {
                 at_span, arms_span, ..
             } => at_span.join_range(arms_span),
+            Markup::Raw {
+                at_span, body_span, ..
+            } => at_span.join_range(body_span),
+            Markup::Comment {
+                at_span, ref body, ..
+            } => at_span.join_range(body.span()),
             Markup::Custom { at_span, .. } => at_span,
         }
     }
@@ -82,10 +176,33 @@ pub enum Attr {
     Id {
         hash_span: SpanRange,
         name: Markup,
+        toggler: Option<Toggler>,
+    },
+    /// An id from `#[expr]`, where `expr` is an `Option`.
+    OptionalId {
+        hash_span: SpanRange,
+        toggler: Toggler,
     },
     Named {
         named_attr: NamedAttr,
     },
+    Dynamic {
+        dynamic_attr: DynamicAttr,
+    },
+    Spread {
+        dots_span: SpanRange,
+        expr: TokenStream,
+        expr_span: SpanRange,
+    },
+    /// Attributes chosen by `@if` or `@match`, e.g.
+    /// `@if cond { href=(url) } @else { disabled }`.
+    Branches {
+        at_span: SpanRange,
+        /// `match expr` for a `@match`, or empty for an `@if`.
+        head: TokenStream,
+        branches: Vec<AttrBranch>,
+        span: SpanRange,
+    },
 }
 
 impl Attr {
@@ -107,15 +224,52 @@ impl Attr {
             Attr::Id {
                 hash_span,
                 ref name,
+                ref toggler,
             } => {
                 let name_span = name.span();
-                hash_span.join_range(name_span)
+                let hash_name_span = hash_span.join_range(name_span);
+                if let Some(toggler) = toggler {
+                    hash_name_span.join_range(toggler.cond_span)
+                } else {
+                    hash_name_span
+                }
             }
+            Attr::OptionalId {
+                hash_span,
+                ref toggler,
+            } => hash_span.join_range(toggler.cond_span),
             Attr::Named { ref named_attr } => named_attr.span(),
+            Attr::Dynamic { ref dynamic_attr } => dynamic_attr.span(),
+            Attr::Spread {
+                dots_span,
+                expr_span,
+                ..
+            } => dots_span.join_range(expr_span),
+            Attr::Branches { at_span, span, .. } => at_span.join_range(span),
+        }
+    }
+
+    /// Returns the name of this attribute if it's known at compile time,
+    /// treating the class and id shorthands as `class` and `id`.
+    pub fn static_name(&self) -> Option<String> {
+        match self {
+            Attr::Class { .. } => Some("class".to_owned()),
+            Attr::Id { .. } | Attr::OptionalId { .. } => Some("id".to_owned()),
+            Attr::Named { named_attr } => Some(name_to_string(named_attr.name.clone())),
+            Attr::Dynamic { .. } | Attr::Spread { .. } | Attr::Branches { .. } => None,
         }
     }
 }
 
+/// One branch of an `Attr::Branches`, e.g. `else if cond { ... }` or
+/// `Some(x) => { ... }`.
+#[derive(Debug)]
+pub struct AttrBranch {
+    pub head: TokenStream,
+    pub attrs: Vec<Attr>,
+    pub body_span: SpanRange,
+}
+
 #[derive(Debug)]
 pub enum ElementBody {
     Void { semi_span: SpanRange },
@@ -174,6 +328,26 @@ impl NamedAttr {
     }
 }
 
+/// An attribute whose name contains splices, e.g. `hx-(verb)=(url)`.
+#[derive(Debug)]
+pub struct DynamicAttr {
+    /// The parts of the name, each either a `Markup::Symbol` or a
+    /// `Markup::Splice`.
+    pub name: Vec<Markup>,
+    pub attr_type: AttrType,
+}
+
+impl DynamicAttr {
+    fn span(&self) -> SpanRange {
+        let name_span = join_ranges(self.name.iter().map(Markup::span));
+        if let Some(attr_type_span) = self.attr_type.span() {
+            name_span.join_range(attr_type_span)
+        } else {
+            name_span
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum AttrType {
     Normal { value: Markup },
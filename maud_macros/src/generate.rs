@@ -36,10 +36,12 @@ impl Generator {
                 markups,
                 outer_span,
             }) => {
-                if markups
-                    .iter()
-                    .any(|markup| matches!(*markup, Markup::Let { .. }))
-                {
+                if markups.iter().any(|markup| {
+                    matches!(
+                        *markup,
+                        Markup::Let { .. } | Markup::Statements { .. } | Markup::LocalFn { .. }
+                    )
+                }) {
                     self.block(
                         Block {
                             markups,
@@ -53,18 +55,61 @@ impl Generator {
             }
             Markup::Literal { content, .. } => build.push_escaped(&content),
             Markup::Symbol { symbol } => self.name(symbol, build),
+            Markup::Entity { entity, .. } => build.push_str(&entity),
             Markup::Splice { expr, .. } => self.splice(expr, build),
             Markup::Element { name, attrs, body } => self.element(name, attrs, body, build),
-            Markup::Let { tokens, .. } => build.push_tokens(tokens),
+            Markup::DynamicElement {
+                expr, attrs, body, ..
+            } => self.dynamic_element(expr, attrs, body, build),
+            Markup::Let {
+                tokens, else_body, ..
+            } => match else_body {
+                Some(else_body) => {
+                    build.push_tokens(quote!(#tokens else));
+                    self.block(else_body, build);
+                    build.push_tokens(quote!(;));
+                }
+                None => build.push_tokens(tokens),
+            },
+            Markup::Statements { stmts, .. } => build.push_tokens(stmts),
             Markup::Special { segments } => {
                 for Special { head, body, .. } in segments {
                     build.push_tokens(head);
                     self.block(body, build);
                 }
             }
+            Markup::LocalFn {
+                name, params, body, ..
+            } => self.local_fn(name, params, body, build),
+            Markup::LocalCall {
+                name,
+                args,
+                body,
+                lazy,
+                ..
+            } => self.local_call(name, args, body, lazy, build),
             Markup::Custom {
-                name, expr, body, ..
-            } => self.custom_element(name, expr, body, build),
+                name,
+                expr,
+                attrs,
+                body,
+                lazy,
+                ..
+            } => self.custom_element(name, expr, attrs, body, lazy, build),
+            Markup::Raw { content, .. } => build.push_str(&content),
+            Markup::Comment { body, .. } => self.comment(body, build),
+            Markup::For {
+                pattern,
+                iter,
+                meta,
+                body,
+                separator,
+                empty,
+                ..
+            } => {
+                let (pattern, iter) = for_head(pattern, iter, meta);
+                self.for_expr(pattern, iter, body, separator, empty, build);
+            }
             Markup::Match {
                 head,
                 arms,
@@ -104,45 +149,270 @@ impl Generator {
         build.push_tokens(TokenStream::from(block));
     }
 
+    fn for_expr(
+        &self,
+        pattern: TokenStream,
+        iter: TokenStream,
+        body: Block,
+        separator: Option<Block>,
+        empty: Option<Block>,
+        build: &mut Builder,
+    ) {
+        if separator.is_none() && empty.is_none() {
+            build.push_tokens(quote!(for #pattern in #iter));
+            self.block(body, build);
+            return;
+        }
+        // Track whether we're on the first iteration, so that the iterator
+        // doesn't need to be collected or peeked
+        let first_ident = TokenTree::Ident(Ident::new("__maud_first", Span::mixed_site()));
+        let loop_body = {
+            let mut build = self.builder();
+            if let Some(separator) = separator {
+                build.push_tokens(quote!(if !#first_ident));
+                self.block(separator, &mut build);
+            }
+            build.push_tokens(quote!(#first_ident = false;));
+            self.block(body, &mut build);
+            build.finish()
+        };
+        let mut stmts = quote! {
+            let mut #first_ident = true;
+            for #pattern in #iter { #loop_body }
+        };
+        if let Some(empty) = empty {
+            let mut build = self.builder();
+            build.push_tokens(quote!(if #first_ident));
+            self.block(empty, &mut build);
+            stmts.extend(build.finish());
+        }
+        build.push_tokens(quote!({ #stmts }));
+    }
+
+    fn comment(&self, body: Block, build: &mut Builder) {
+        if cfg!(feature = "strip_comments") {
+            let comment = {
+                let mut build = self.builder();
+                self.comment_body(body, &mut build);
+                build.finish()
+            };
+            build.push_tokens(quote!(if ::core::cfg!(debug_assertions) { #comment }));
+        } else {
+            self.comment_body(body, build);
+        }
+    }
+
+    fn comment_body(&self, body: Block, build: &mut Builder) {
+        let output_ident = self.output_ident.clone();
+        build.push_str("<!--");
+        for markup in body.markups {
+            match markup {
+                // Literals have been checked by the parser, and aren't escaped
+                // since comments don't support character references
+                Markup::Literal { content, .. } => build.push_str(&content),
+                Markup::Splice { expr, .. } => build.push_tokens(quote!(
+                    maud::comment::render_to(&(#expr), #output_ident);
+                )),
+                _ => {}
+            }
+        }
+        build.push_str("-->");
+    }
+
     fn splice(&self, expr: TokenStream, build: &mut Builder) {
         let output_ident = self.output_ident.clone();
-        build.push_tokens(quote!(maud::Render::render_to(&#expr, &mut #output_ident);));
+        build.push_tokens(quote!(maud::Render::render_to(&#expr, #output_ident);));
+    }
+
+    /// Defines a component from `@fn` as a nested function, which takes
+    /// the output buffer as its first argument.
+    fn local_fn(&self, name: Ident, params: TokenStream, body: Block, build: &mut Builder) {
+        let output_ident = self.output_ident.clone();
+        let mut block = self.builder();
+        self.block(body, &mut block);
+        let block = block.finish();
+        build.push_tokens(quote!(
+            fn #name(#output_ident: &mut ::std::string::String, #params) #block
+        ));
+    }
+
+    fn local_call(
+        &self,
+        name: Ident,
+        args: TokenStream,
+        body: ElementBody,
+        lazy: bool,
+        build: &mut Builder,
+    ) {
+        let output_ident = self.output_ident.clone();
+        let mut args = match args.clone().into_iter().last() {
+            None => quote!(#output_ident),
+            _ => quote!(#output_ident, #args),
+        };
+        if let ElementBody::Block { block } = body {
+            let children = self.component_body(block, lazy);
+            args = match args.clone().into_iter().last() {
+                Some(TokenTree::Punct(p)) if p.as_char() == ',' => quote!(#args #children),
+                _ => quote!(#args, #children),
+            };
+        }
+        build.push_tokens(quote!(#name(#args);));
     }
 
     fn custom_element(
         &self,
         name: TokenStream,
         expr: TokenStream,
+        attrs: Option<Vec<Attr>>,
         body: ElementBody,
+        lazy: bool,
         build: &mut Builder,
     ) {
         let output_ident = self.output_ident.clone();
 
+        // Attributes and body are passed after the arguments written in the
+        // template, in that order
+        let mut extra_args = Vec::new();
+        if let Some(attrs) = attrs {
+            extra_args.push(self.attributes_value(attrs));
+        }
         if let ElementBody::Block { block } = body {
-            let inner = {
-                let mut build = Builder::new(output_ident.clone());
-                Generator::new(output_ident.clone()).markups(block.markups, &mut build);
-                let stmts = build.finish();
-                quote!({
-                    // TODO add something to predict size of this
-                    let mut #output_ident = String::with_capacity(20);
-                    #stmts
-                    maud::PreEscaped(#output_ident)
-                })
-            };
-            build.push_tokens(match expr.clone().into_iter().last() {
-                Some(TokenTree::Punct(p)) if p.as_char() == ',' => {
-                    quote!(maud::Render::render_to(&#name(#expr #inner), &mut #output_ident);)
+            extra_args.push(self.component_body(block, lazy));
+        }
+
+        let args = match expr.clone().into_iter().last() {
+            None => quote!(#(#extra_args),*),
+            Some(TokenTree::Punct(p)) if p.as_char() == ',' => quote!(#expr #(#extra_args),*),
+            _ if extra_args.is_empty() => expr,
+            _ => quote!(#expr, #(#extra_args),*),
+        };
+        build.push_tokens(quote!(maud::Render::render_to(&#name(#args), #output_ident);));
+    }
+
+    /// Returns the expression for a component body: a `maud::Children`
+    /// that writes into whichever buffer it's spliced into if the body is
+    /// lazy, or else a `Markup`.
+    fn component_body(&self, block: Block, lazy: bool) -> TokenStream {
+        if !lazy {
+            return self.render_to_string(block.markups);
+        }
+        let output_ident = self.output_ident.clone();
+        let mut build = self.builder();
+        self.markups(block.markups, &mut build);
+        let stmts = build.finish();
+        // The closure is `FnOnce`, so the body can move the values it
+        // captures, like a `@for` over an owned `Vec`
+        quote!(maud::Children::new(&mut ::std::option::Option::Some(
+            |#output_ident: &mut ::std::string::String| { #stmts }
+        )))
+    }
+
+    /// Collects the attributes passed to a component into a
+    /// `maud::Attributes` expression.
+    fn attributes_value(&self, attrs: Vec<Attr>) -> TokenStream {
+        if attrs.is_empty() {
+            return quote!(maud::Attributes::new());
+        }
+        let attrs_ident = TokenTree::Ident(Ident::new("__maud_attrs", Span::mixed_site()));
+        let DesugaredAttrs {
+            named,
+            conditional_ids,
+            spreads,
+            branches,
+        } = desugar_attrs(attrs, false);
+        let named_inserts = named.into_iter().map(|attr| {
+            let NamedAttr { name, attr_type } = match attr {
+                NamedOrDynamic::Named(named_attr) => named_attr,
+                NamedOrDynamic::Dynamic(DynamicAttr { name, attr_type }) => {
+                    return self.attribute_insert(&attrs_ident, dynamic_name(name), attr_type);
                 }
-                _ => {
-                    quote!(maud::Render::render_to(&#name(#expr, #inner), &mut #output_ident);)
+                NamedOrDynamic::Merged(merged_attr) => {
+                    let name = name_to_string(merged_attr.name.clone());
+                    let set = |value| quote!(#attrs_ident.insert(#name, maud::PreEscaped(#value)););
+                    return self.merged_attr(merged_attr, None, &set);
                 }
-            });
-        } else {
-            build.push_tokens(quote!(maud::Render::render_to(&#name(#expr), &mut #output_ident);));
+            };
+            let name = name_to_string(name);
+            match attr_type {
+                AttrType::Normal {
+                    value: Markup::Splice { expr, outer_span },
+                } if is_boolean_attr(&name) => {
+                    let on = quote!(#attrs_ident.insert(#name, ""););
+                    let with_value = |value| quote!(#attrs_ident.insert(#name, #value););
+                    self.boolean_attr(expr, outer_span, on, &with_value)
+                }
+                attr_type => self.attribute_insert(&attrs_ident, quote!(#name), attr_type),
+            }
+        });
+        let id_insert = conditional_ids.map(|ids| {
+            let set_id = |value| {
+                self.attribute_insert(&attrs_ident, quote!("id"), AttrType::Normal { value })
+            };
+            self.conditional_ids(ids, &set_id, TokenStream::new())
+        });
+        let branch_inserts = branches.into_iter().map(|(head, group, span)| {
+            self.attr_branches(head, group, span, &|attrs| {
+                let value = self.attributes_value(attrs);
+                quote!(maud::Attributes::extend(&mut #attrs_ident, #value);)
+            })
+        });
+        let inserts = named_inserts
+            .chain(id_insert)
+            .chain(branch_inserts)
+            .collect::<Vec<_>>();
+        // Spread attributes go first, so that the ones written in the
+        // template take precedence
+        quote!({
+            let mut #attrs_ident = maud::Attributes::new();
+            #(maud::Attributes::extend(&mut #attrs_ident, #spreads);)*
+            #(#inserts)*
+            #attrs_ident
+        })
+    }
+
+    /// Inserts a single attribute into a `maud::Attributes`.
+    fn attribute_insert(
+        &self,
+        attrs_ident: &TokenTree,
+        name: TokenStream,
+        attr_type: AttrType,
+    ) -> TokenStream {
+        match attr_type {
+            AttrType::Normal { value } => {
+                let value = self.render_to_string(vec![value]);
+                quote!(#attrs_ident.insert(#name, #value);)
+            }
+            AttrType::Optional {
+                toggler: Toggler { cond, .. },
+            } => quote!(if let Some(inner_value) = (#cond) {
+                #attrs_ident.insert(#name, inner_value);
+            }),
+            AttrType::Empty { toggler: None } => quote!(#attrs_ident.insert(#name, "");),
+            AttrType::Empty {
+                toggler: Some(Toggler { cond, .. }),
+            } => quote!(if (#cond) {
+                #attrs_ident.insert(#name, "");
+            }),
         }
     }
 
+    /// Renders some markup into a `maud::PreEscaped<String>` expression.
+    fn render_to_string(&self, markups: Vec<Markup>) -> TokenStream {
+        let buffer_ident = TokenTree::Ident(Ident::new("__maud_buffer", Span::mixed_site()));
+        let output_ident = self.output_ident.clone();
+        let stmts = {
+            let mut build = self.builder();
+            self.markups(markups, &mut build);
+            build.finish()
+        };
+        quote!({
+            let mut #buffer_ident = String::with_capacity(20);
+            let #output_ident = &mut #buffer_ident;
+            #stmts
+            maud::PreEscaped(#buffer_ident)
+        })
+    }
+
     fn element(
         &self,
         name: TokenStream,
@@ -191,57 +461,452 @@ impl Generator {
         self.attrs(attrs, build);
         build.push_str(">");
         if let ElementBody::Block { block } = body {
-            self.markups(block.markups, build);
+            // Give each body its own scope, so that sibling elements can
+            // define `@fn`s with the same name
+            if block
+                .markups
+                .iter()
+                .any(|markup| matches!(*markup, Markup::LocalFn { .. }))
+            {
+                self.block(block, build);
+            } else {
+                self.markups(block.markups, build);
+            }
             build.push_str("</");
             self.name(name, build);
             build.push_str(">");
         }
     }
 
+    fn dynamic_element(
+        &self,
+        expr: TokenStream,
+        attrs: Vec<Attr>,
+        body: ElementBody,
+        build: &mut Builder,
+    ) {
+        let output_ident = self.output_ident.clone();
+        let tag_ident = TokenTree::Ident(Ident::new("__maud_tag", Span::mixed_site()));
+        let push_name = quote!(#output_ident.push_str(#tag_ident););
+        let open = {
+            let mut build = self.builder();
+            build.push_str("<");
+            build.push_tokens(push_name.clone());
+            self.attrs(attrs, &mut build);
+            build.push_str(">");
+            build.finish()
+        };
+        let (body, close) = match body {
+            ElementBody::Block { block } => {
+                let mut body = self.builder();
+                self.markups(block.markups, &mut body);
+                let mut close = self.builder();
+                close.push_str("</");
+                close.push_tokens(push_name);
+                close.push_str(">");
+                let close = close.finish();
+                (
+                    body.finish(),
+                    quote!(if let Some(#tag_ident) = #tag_ident { #close }),
+                )
+            }
+            ElementBody::Void { .. } => (TokenStream::new(), TokenStream::new()),
+        };
+        // An invalid name leaves out the tags, but keeps the contents. The
+        // whole element is wrapped in a block, so that nested elements don't
+        // shadow the name we need for the closing tag
+        build.push_tokens(quote!({
+            let #tag_ident = &(#expr);
+            let #tag_ident = maud::TagName::tag_name(#tag_ident);
+            if let Some(#tag_ident) = #tag_ident { #open }
+            #body
+            #close
+        }));
+    }
+
     fn name(&self, name: TokenStream, build: &mut Builder) {
         build.push_escaped(&name_to_string(name));
     }
 
     fn attrs(&self, attrs: Vec<Attr>, build: &mut Builder) {
-        for NamedAttr { name, attr_type } in desugar_attrs(attrs) {
-            match attr_type {
-                AttrType::Normal { value } => {
-                    build.push_str(" ");
-                    self.name(name, build);
-                    build.push_str("=\"");
-                    self.markup(value, build);
-                    build.push_str("\"");
+        self.attrs_with_spread(attrs, None, build);
+    }
+
+    /// Renders attributes, some of which may be inside an `@if` or `@match`
+    /// whose element has spread attributes in `outer_spread`.
+    ///
+    /// Spreads in a branch are added to the outer ones, and the names the
+    /// branch sets are taken out of them, so that they're only left out of
+    /// the spread if the branch is taken.
+    fn attrs_with_spread(
+        &self,
+        attrs: Vec<Attr>,
+        outer_spread: Option<&TokenTree>,
+        build: &mut Builder,
+    ) {
+        let output_ident = self.output_ident.clone();
+        let DesugaredAttrs {
+            named,
+            conditional_ids,
+            spreads,
+            branches,
+        } = desugar_attrs(attrs, outer_spread.is_some());
+        // Collect all the spread attributes up front, so that `class` and
+        // `style` can be merged into the attributes written in the template
+        let spread_ident = match outer_spread {
+            _ if spreads.is_empty() => outer_spread.cloned(),
+            Some(outer_spread) => {
+                build.push_tokens(quote! {
+                    #(maud::Attributes::extend(&mut #outer_spread, #spreads);)*
+                });
+                Some(outer_spread.clone())
+            }
+            None => {
+                let spread_ident =
+                    TokenTree::Ident(Ident::new("__maud_spread", Span::mixed_site()));
+                build.push_tokens(quote! {
+                    let mut #spread_ident = maud::Attributes::new();
+                    #(maud::Attributes::extend(&mut #spread_ident, #spreads);)*
+                });
+                Some(spread_ident)
+            }
+        };
+        let mut static_names = named
+            .iter()
+            .filter_map(|attr| match attr {
+                NamedOrDynamic::Named(NamedAttr { name, .. })
+                | NamedOrDynamic::Merged(MergedAttr { name, .. }) => {
+                    Some(name_to_string(name.clone()))
+                }
+                NamedOrDynamic::Dynamic(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if conditional_ids.is_some() {
+            static_names.push("id".to_owned());
+        }
+        for attr in named {
+            let NamedAttr { name, attr_type } = match attr {
+                NamedOrDynamic::Named(named_attr) => named_attr,
+                NamedOrDynamic::Dynamic(dynamic_attr) => {
+                    self.dynamic_attr(dynamic_attr, spread_ident.as_ref(), build);
+                    continue;
                 }
-                AttrType::Optional {
-                    toggler: Toggler { cond, .. },
-                } => {
-                    let inner_value = quote!(inner_value);
-                    let body = {
+                NamedOrDynamic::Merged(merged_attr) => {
+                    let name = merged_attr.name.clone();
+                    let set = |value| {
                         let mut build = self.builder();
                         build.push_str(" ");
-                        self.name(name, &mut build);
+                        self.name(name.clone(), &mut build);
                         build.push_str("=\"");
-                        self.splice(inner_value.clone(), &mut build);
+                        build.push_tokens(quote!(#output_ident.push_str(&#value);));
                         build.push_str("\"");
                         build.finish()
                     };
-                    build.push_tokens(quote!(if let Some(#inner_value) = (#cond) { #body }));
-                }
-                AttrType::Empty { toggler: None } => {
-                    build.push_str(" ");
-                    self.name(name, build);
+                    build.push_tokens(self.merged_attr(merged_attr, spread_ident.as_ref(), &set));
+                    continue;
                 }
-                AttrType::Empty {
-                    toggler: Some(Toggler { cond, .. }),
-                } => {
-                    let body = {
+            };
+            let name_string = name_to_string(name.clone());
+            match attr_type {
+                AttrType::Normal {
+                    value: Markup::Splice { expr, outer_span },
+                } if is_boolean_attr(&name_string) => {
+                    let on = {
                         let mut build = self.builder();
                         build.push_str(" ");
-                        self.name(name, &mut build);
+                        self.name(name.clone(), &mut build);
+                        build.finish()
+                    };
+                    let with_value = |value| {
+                        let mut build = self.builder();
+                        build.push_str(" ");
+                        self.name(name.clone(), &mut build);
+                        build.push_str("=\"");
+                        self.splice(value, &mut build);
+                        build.push_str("\"");
                         build.finish()
                     };
-                    build.push_tokens(quote!(if (#cond) { #body }));
+                    build.push_tokens(self.boolean_attr(expr, outer_span, on, &with_value));
                 }
+                attr_type => self.attr(&|build| self.name(name.clone(), build), attr_type, build),
+            }
+        }
+        if let Some(ids) = conditional_ids {
+            let set_id = |value| {
+                let mut build = self.builder();
+                build.push_str(" id=\"");
+                self.markup(value, &mut build);
+                build.push_str("\"");
+                build.finish()
+            };
+            // Fall back to a spread id, if there is one
+            let otherwise = match spread_ident {
+                Some(ref spread_ident) => {
+                    let value_ident = TokenTree::Ident(Ident::new("__maud_id", Span::mixed_site()));
+                    quote!(if let Some(#value_ident) = #spread_ident.get("id") {
+                        #output_ident.push_str(" id=\"");
+                        #output_ident.push_str(#value_ident);
+                        #output_ident.push_str("\"");
+                    })
+                }
+                None => TokenStream::new(),
+            };
+            build.push_tokens(self.conditional_ids(ids, &set_id, otherwise));
+        }
+        for (head, group, span) in branches {
+            build.push_tokens(self.attr_branches(head, group, span, &|attrs| {
+                let mut build = self.builder();
+                self.attrs_with_spread(attrs, spread_ident.as_ref(), &mut build);
+                build.finish()
+            }));
+        }
+        // Attributes written in the template take precedence
+        match spread_ident {
+            Some(spread_ident) if outer_spread.is_some() => build.push_tokens(quote!(
+                #(#spread_ident.remove(#static_names);)*
+            )),
+            Some(spread_ident) => build.push_tokens(quote!(
+                #spread_ident.render_except_to(&[#(#static_names),*], #output_ident);
+            )),
+            None => {}
+        }
+    }
+
+    /// Renders an attribute whose name is only known at runtime.
+    ///
+    /// The name is taken out of the spread attributes, if there are any, so
+    /// that it isn't rendered twice.
+    fn dynamic_attr(
+        &self,
+        DynamicAttr { name, attr_type }: DynamicAttr,
+        spread_ident: Option<&TokenTree>,
+        build: &mut Builder,
+    ) {
+        let output_ident = self.output_ident.clone();
+        let name_ident = TokenTree::Ident(Ident::new("__maud_attr_name", Span::mixed_site()));
+        let name_expr = dynamic_name(name);
+        let body = {
+            let mut build = self.builder();
+            let push_name = |build: &mut Builder| {
+                build.push_tokens(quote!(
+                    maud::Render::render_to(#name_ident.as_str(), #output_ident);
+                ));
+            };
+            self.attr(&push_name, attr_type, &mut build);
+            build.finish()
+        };
+        let remove = spread_ident.map(|spread_ident| quote!(#spread_ident.remove(&#name_ident);));
+        // Leave out attributes whose names would break the markup
+        build.push_tokens(quote!({
+            let #name_ident = #name_expr;
+            if maud::Attributes::is_valid_name(&#name_ident) {
+                #body
+                #remove
+            }
+        }));
+    }
+
+    /// Renders the parts of a merged `class` or `style` into a `String`,
+    /// then passes it to `set` if any of them were present.
+    ///
+    /// Whether a separator is needed is only known at runtime, since parts
+    /// can be toggled off. If given, the value from `spread_ident` is merged
+    /// in last.
+    fn merged_attr(
+        &self,
+        MergedAttr { name, parts }: MergedAttr,
+        spread_ident: Option<&TokenTree>,
+        set: &dyn Fn(TokenStream) -> TokenStream,
+    ) -> TokenStream {
+        let output_ident = self.output_ident.clone();
+        let merged_ident = TokenTree::Ident(Ident::new("__maud_merged", Span::mixed_site()));
+        let separate_ident = TokenTree::Ident(Ident::new("__maud_separate", Span::mixed_site()));
+        let value_ident = TokenTree::Ident(Ident::new("__maud_value", Span::mixed_site()));
+        let name = name_to_string(name);
+        let part_stmts = parts.into_iter().map(|part| {
+            let mut build = self.builder();
+            build.push_tokens(quote! {
+                if #separate_ident {
+                    maud::Attributes::push_separator(#name, #output_ident);
+                }
+                #separate_ident = true;
+            });
+            match part {
+                MergedPart::Always { value } => {
+                    self.markup(value, &mut build);
+                    build.finish()
+                }
+                MergedPart::Toggled { value, cond } => {
+                    self.markup(value, &mut build);
+                    let body = build.finish();
+                    quote!(if (#cond) { #body })
+                }
+                MergedPart::Optional { expr, expr_span } => {
+                    self.markup(
+                        Markup::Splice {
+                            expr: quote!(#value_ident),
+                            outer_span: expr_span,
+                        },
+                        &mut build,
+                    );
+                    let body = build.finish();
+                    quote!(if let Some(#value_ident) = (#expr) { #body })
+                }
+            }
+        });
+        let spread_stmt = spread_ident.map(|spread_ident| {
+            quote!(#spread_ident.render_merged_to(#name, &mut #separate_ident, #output_ident);)
+        });
+        let set = set(quote!(#merged_ident));
+        quote!({
+            let mut #merged_ident = ::std::string::String::new();
+            let mut #separate_ident = false;
+            {
+                let #output_ident = &mut #merged_ident;
+                #(#part_stmts)*
+                #spread_stmt
+            }
+            if #separate_ident {
+                #set
+            }
+        })
+    }
+
+    /// Builds the branches of an `@if` or `@match` in attribute position,
+    /// using `body` to build the statements for each set of attributes.
+    ///
+    /// `head` is the `match expr` of a `@match`, whose arms are wrapped in
+    /// braces spanning `span`, or empty for an `@if`.
+    fn attr_branches(
+        &self,
+        head: TokenStream,
+        group: Vec<AttrBranch>,
+        span: SpanRange,
+        body: &dyn Fn(Vec<Attr>) -> TokenStream,
+    ) -> TokenStream {
+        let mut tokens = TokenStream::new();
+        for AttrBranch {
+            head,
+            attrs,
+            body_span,
+        } in group
+        {
+            let mut block = TokenTree::Group(Group::new(Delimiter::Brace, body(attrs)));
+            block.set_span(body_span.collapse());
+            tokens.extend(quote!(#head #block));
+        }
+        if head.is_empty() {
+            tokens
+        } else {
+            let mut arms = TokenTree::Group(Group::new(Delimiter::Brace, tokens));
+            arms.set_span(span.collapse());
+            quote!(#head #arms)
+        }
+    }
+
+    /// Chains the conditional ids of an element into an `if` expression,
+    /// using `set_id` to build the statements that apply an id.
+    ///
+    /// `otherwise` runs if none of the ids apply.
+    fn conditional_ids(
+        &self,
+        ConditionalIds {
+            conditional,
+            fallback,
+        }: ConditionalIds,
+        set_id: &dyn Fn(Markup) -> TokenStream,
+        otherwise: TokenStream,
+    ) -> TokenStream {
+        let value_ident = TokenTree::Ident(Ident::new("__maud_id", Span::mixed_site()));
+        let mut chain = TokenStream::new();
+        for id in conditional {
+            match id {
+                ConditionalId::Toggled { name, cond } => {
+                    let body = set_id(name);
+                    chain.extend(quote!(if (#cond) { #body } else));
+                }
+                ConditionalId::Optional { expr, expr_span } => {
+                    let body = set_id(Markup::Splice {
+                        expr: quote!(#value_ident),
+                        outer_span: expr_span,
+                    });
+                    chain.extend(quote!(if let Some(#value_ident) = (#expr) { #body } else));
+                }
+            }
+        }
+        let fallback = match fallback {
+            Some(name) => set_id(name),
+            None => otherwise,
+        };
+        quote!(#chain { #fallback })
+    }
+
+    /// Renders a boolean attribute whose value is the splice `expr`.
+    ///
+    /// If `expr` is a `bool`, `on` runs when it's `true`. Otherwise the
+    /// rendered value is passed to `with_value`, and the user gets a warning
+    /// pointing them to the toggler syntax.
+    fn boolean_attr(
+        &self,
+        expr: TokenStream,
+        expr_span: SpanRange,
+        on: TokenStream,
+        with_value: &dyn Fn(TokenStream) -> TokenStream,
+    ) -> TokenStream {
+        let value_ident = TokenTree::Ident(Ident::new("__maud_value", Span::mixed_site()));
+        let with_value = with_value(quote!(#value_ident));
+        // Give the method call the span of the splice, so that the warning
+        // points there
+        let method = TokenTree::Ident(Ident::new("bool_attr", expr_span.collapse()));
+        quote!({
+            use maud::bool_attr::{ViaBool as _, ViaRender as _};
+            match (&maud::bool_attr::BoolAttr(&(#expr))).#method() {
+                maud::bool_attr::BoolAttrValue::On => { #on }
+                maud::bool_attr::BoolAttrValue::Off => {}
+                maud::bool_attr::BoolAttrValue::Value(#value_ident) => { #with_value }
+            }
+        })
+    }
+
+    /// Renders a single attribute, using `push_name` to write its name.
+    fn attr(&self, push_name: &dyn Fn(&mut Builder), attr_type: AttrType, build: &mut Builder) {
+        match attr_type {
+            AttrType::Normal { value } => {
+                build.push_str(" ");
+                push_name(build);
+                build.push_str("=\"");
+                self.markup(value, build);
+                build.push_str("\"");
+            }
+            AttrType::Optional {
+                toggler: Toggler { cond, .. },
+            } => {
+                let inner_value = quote!(inner_value);
+                let body = {
+                    let mut build = self.builder();
+                    build.push_str(" ");
+                    push_name(&mut build);
+                    build.push_str("=\"");
+                    self.splice(inner_value.clone(), &mut build);
+                    build.push_str("\"");
+                    build.finish()
+                };
+                build.push_tokens(quote!(if let Some(#inner_value) = (#cond) { #body }));
+            }
+            AttrType::Empty { toggler: None } => {
+                build.push_str(" ");
+                push_name(build);
+            }
+            AttrType::Empty {
+                toggler: Some(Toggler { cond, .. }),
+            } => {
+                let body = {
+                    let mut build = self.builder();
+                    build.push_str(" ");
+                    push_name(&mut build);
+                    build.finish()
+                };
+                build.push_tokens(quote!(if (#cond) { #body }));
             }
         }
     }
@@ -249,11 +914,73 @@ impl Generator {
 
 ////////////////////////////////////////////////////////
 
-fn desugar_attrs(attrs: Vec<Attr>) -> Vec<NamedAttr> {
+struct DesugaredAttrs {
+    /// Named attributes in the order they're written, after the merged
+    /// `class` and `id`.
+    named: Vec<NamedOrDynamic>,
+    /// Set instead of an `id` in `named` if any of the ids are conditional.
+    conditional_ids: Option<ConditionalIds>,
+    spreads: Vec<TokenStream>,
+    /// Groups of attributes from `@if` and `@match`, with their heads.
+    branches: Vec<(TokenStream, Vec<AttrBranch>, SpanRange)>,
+}
+
+enum NamedOrDynamic {
+    Named(NamedAttr),
+    Dynamic(DynamicAttr),
+    Merged(MergedAttr),
+}
+
+/// A `class` or `style` made out of several parts, some of which are only
+/// present at runtime.
+struct MergedAttr {
+    name: TokenStream,
+    parts: Vec<MergedPart>,
+}
+
+enum MergedPart {
+    Always {
+        value: Markup,
+    },
+    Toggled {
+        value: Markup,
+        cond: TokenStream,
+    },
+    Optional {
+        expr: TokenStream,
+        expr_span: SpanRange,
+    },
+}
+
+/// The ids of an element with toggled (`#(id)[cond]`) or optional
+/// (`#[opt_id]`) ids. The first one that applies is used, or the
+/// unconditional id if none do.
+struct ConditionalIds {
+    conditional: Vec<ConditionalId>,
+    fallback: Option<Markup>,
+}
+
+enum ConditionalId {
+    Toggled {
+        name: Markup,
+        cond: TokenStream,
+    },
+    Optional {
+        expr: TokenStream,
+        expr_span: SpanRange,
+    },
+}
+
+/// `has_outer_spread` is set if the attributes are in a branch of an
+/// element with spread attributes.
+fn desugar_attrs(attrs: Vec<Attr>, has_outer_spread: bool) -> DesugaredAttrs {
     let mut classes_static = vec![];
     let mut classes_toggled = vec![];
     let mut ids = vec![];
+    let mut conditional_ids = vec![];
     let mut named_attrs = vec![];
+    let mut spreads = vec![];
+    let mut branches = vec![];
     for attr in attrs {
         match attr {
             Attr::Class {
@@ -266,51 +993,234 @@ fn desugar_attrs(attrs: Vec<Attr>) -> Vec<NamedAttr> {
                 toggler: None,
                 ..
             } => classes_static.push(name),
-            Attr::Id { name, .. } => ids.push(name),
-            Attr::Named { named_attr } => named_attrs.push(named_attr),
+            Attr::Id {
+                name,
+                toggler: Some(Toggler { cond, .. }),
+                ..
+            } => conditional_ids.push(ConditionalId::Toggled { name, cond }),
+            Attr::Id {
+                name,
+                toggler: None,
+                ..
+            } => ids.push(name),
+            Attr::OptionalId {
+                toggler: Toggler { cond, cond_span },
+                ..
+            } => conditional_ids.push(ConditionalId::Optional {
+                expr: cond,
+                expr_span: cond_span,
+            }),
+            Attr::Named { named_attr } => named_attrs.push(NamedOrDynamic::Named(named_attr)),
+            Attr::Dynamic { dynamic_attr } => {
+                named_attrs.push(NamedOrDynamic::Dynamic(dynamic_attr));
+            }
+            Attr::Spread { expr, .. } => spreads.push(expr),
+            Attr::Branches {
+                head,
+                branches: group,
+                span,
+                ..
+            } => branches.push((head, group, span)),
         }
     }
-    let classes = desugar_classes_or_ids("class", classes_static, classes_toggled);
-    let ids = desugar_classes_or_ids("id", ids, vec![]);
-    classes.into_iter().chain(ids).chain(named_attrs).collect()
+    let (ids, conditional_ids) = if conditional_ids.is_empty() {
+        (desugar_ids(ids), None)
+    } else {
+        let conditional_ids = ConditionalIds {
+            conditional: conditional_ids,
+            // The parser rejects more than one unconditional id
+            fallback: ids.pop(),
+        };
+        (None, Some(conditional_ids))
+    };
+    let named = ids
+        .into_iter()
+        .map(NamedOrDynamic::Named)
+        .chain(named_attrs)
+        .collect();
+    let classes = classes_static
+        .into_iter()
+        .map(|value| MergedPart::Always { value })
+        .chain(
+            classes_toggled
+                .into_iter()
+                .map(|(value, Toggler { cond, .. })| MergedPart::Toggled { value, cond }),
+        )
+        .collect();
+    let has_spread = has_outer_spread || !spreads.is_empty();
+    let named = merge_named_attrs(named, "class", classes, has_spread);
+    let named = merge_named_attrs(named, "style", vec![], has_spread);
+    DesugaredAttrs {
+        named,
+        conditional_ids,
+        spreads,
+        branches,
+    }
 }
 
-fn desugar_classes_or_ids(
-    attr_name: &'static str,
-    values_static: Vec<Markup>,
-    values_toggled: Vec<(Markup, Toggler)>,
-) -> Option<NamedAttr> {
-    if values_static.is_empty() && values_toggled.is_empty() {
-        return None;
+/// Merges every attribute called `attr_name` into a single attribute, in
+/// place of the first one.
+///
+/// This is how `div.card class=(extra)` ends up with a single `class`.
+/// `leading` are parts that go before the attributes, i.e. class shorthand.
+/// Values are separated by a space, or a `;` for `style` if the value so far
+/// doesn't already end with one. If every part is always present, and
+/// there's no spread to merge with, the separators are added at compile
+/// time. Otherwise the attribute becomes a `MergedAttr`, which works them
+/// out at runtime with `Attributes::push_separator`.
+///
+/// Class shorthand with no `class=` attributes or spread keeps the plain
+/// output from before attributes were merged.
+fn merge_named_attrs(
+    attrs: Vec<NamedOrDynamic>,
+    attr_name: &str,
+    leading: Vec<MergedPart>,
+    has_spread: bool,
+) -> Vec<NamedOrDynamic> {
+    let is_target = |attr: &NamedAttr| name_to_string(attr.name.clone()) == attr_name;
+    let count = attrs
+        .iter()
+        .filter(|attr| matches!(attr, NamedOrDynamic::Named(named_attr) if is_target(named_attr)))
+        .count();
+    if leading.is_empty() && (count == 0 || count == 1 && !has_spread) {
+        return attrs;
     }
-    let mut markups = Vec::new();
-    let mut leading_space = false;
-    for name in values_static {
-        markups.extend(prepend_leading_space(name, &mut leading_space));
-    }
-    for (name, Toggler { cond, cond_span }) in values_toggled {
-        let body = Block {
-            markups: prepend_leading_space(name, &mut leading_space),
-            // TODO: is this correct?
-            outer_span: cond_span,
+    let mut result = Vec::new();
+    let mut index = if leading.is_empty() { None } else { Some(0) };
+    let mut parts = leading;
+    for attr in attrs {
+        let attr = match attr {
+            NamedOrDynamic::Named(named_attr) if is_target(&named_attr) => named_attr,
+            attr => {
+                result.push(attr);
+                continue;
+            }
         };
-        markups.push(Markup::Special {
-            segments: vec![Special {
-                at_span: SpanRange::call_site(),
-                head: quote!(if (#cond)),
-                body,
-            }],
-        });
+        index.get_or_insert(result.len());
+        match attr.attr_type {
+            AttrType::Normal { value } => parts.push(MergedPart::Always { value }),
+            AttrType::Optional {
+                toggler: Toggler { cond, cond_span },
+            } => parts.push(MergedPart::Optional {
+                expr: cond,
+                expr_span: cond_span,
+            }),
+            // An empty attribute doesn't add anything to the value
+            AttrType::Empty { .. } => {}
+        }
     }
-    Some(NamedAttr {
-        name: TokenStream::from(TokenTree::Ident(Ident::new(attr_name, Span::call_site()))),
-        attr_type: AttrType::Normal {
-            value: Markup::Block(Block {
-                markups,
-                outer_span: SpanRange::call_site(),
+    let index = index.expect("at least one attribute to merge");
+    let name = TokenStream::from(TokenTree::Ident(Ident::new(attr_name, Span::call_site())));
+    let merged = if count == 0 && !has_spread {
+        // Only class shorthand, which renders the attribute even if every
+        // class is toggled off
+        let mut markups = Vec::new();
+        let mut leading_space = false;
+        for part in parts {
+            match part {
+                MergedPart::Always { value } => {
+                    markups.extend(prepend_leading_space(value, &mut leading_space));
+                }
+                MergedPart::Toggled { value, cond } => {
+                    let outer_span = value.span();
+                    markups.push(Markup::Special {
+                        segments: vec![Special {
+                            at_span: SpanRange::call_site(),
+                            head: quote!(if (#cond)),
+                            body: Block {
+                                markups: prepend_leading_space(value, &mut leading_space),
+                                outer_span,
+                            },
+                        }],
+                    });
+                }
+                MergedPart::Optional { .. } => unreachable!("class shorthand is never optional"),
+            }
+        }
+        NamedOrDynamic::Named(NamedAttr {
+            name,
+            attr_type: AttrType::Normal {
+                value: Markup::Block(Block {
+                    markups,
+                    outer_span: SpanRange::call_site(),
+                }),
+            },
+        })
+    } else {
+        match join_always_present(parts, attr_name, has_spread) {
+            Ok(value) => NamedOrDynamic::Named(NamedAttr {
+                name,
+                attr_type: AttrType::Normal { value },
             }),
-        },
-    })
+            Err(parts) => NamedOrDynamic::Merged(MergedAttr { name, parts }),
+        }
+    };
+    result.insert(index, merged);
+    result
+}
+
+/// Joins the parts of a merged attribute at compile time, if they're all
+/// always present and there's no spread to merge with.
+///
+/// A `style` is only joined if every part is a literal, since whether it
+/// needs a `;` depends on how the value before it ends.
+fn join_always_present(
+    parts: Vec<MergedPart>,
+    attr_name: &str,
+    has_spread: bool,
+) -> Result<Markup, Vec<MergedPart>> {
+    let is_style = attr_name == "style";
+    let joinable = !has_spread
+        && parts.iter().all(|part| match part {
+            MergedPart::Always {
+                value: Markup::Literal { .. },
+            } => true,
+            MergedPart::Always { .. } => !is_style,
+            _ => false,
+        });
+    if !joinable {
+        return Err(parts);
+    }
+    let values = parts.into_iter().map(|part| match part {
+        MergedPart::Always { value } => value,
+        _ => unreachable!(),
+    });
+    if is_style {
+        let mut content = String::new();
+        let mut span = SpanRange::call_site();
+        for (i, value) in values.enumerate() {
+            if let Markup::Literal {
+                content: value,
+                span: value_span,
+            } = value
+            {
+                if i == 0 {
+                    span = value_span;
+                } else {
+                    span.last = value_span.last;
+                    if !content.trim_end().ends_with(';') {
+                        content.push(';');
+                    }
+                }
+                content.push_str(&value);
+            }
+        }
+        return Ok(Markup::Literal { content, span });
+    }
+    let mut markups = Vec::new();
+    for value in values {
+        if !markups.is_empty() {
+            markups.push(Markup::Literal {
+                content: " ".to_owned(),
+                span: value.span(),
+            });
+        }
+        markups.push(value);
+    }
+    Ok(Markup::Block(Block {
+        markups,
+        outer_span: SpanRange::call_site(),
+    }))
 }
 
 fn prepend_leading_space(name: Markup, leading_space: &mut bool) -> Vec<Markup> {
@@ -326,6 +1236,100 @@ fn prepend_leading_space(name: Markup, leading_space: &mut bool) -> Vec<Markup>
     markups
 }
 
+/// Builds a `String` expression out of the parts of a dynamic attribute
+/// name.
+fn dynamic_name(parts: Vec<Markup>) -> TokenStream {
+    let mut format_str = String::new();
+    let mut args = Vec::new();
+    for part in parts {
+        match part {
+            Markup::Symbol { symbol } => format_str.push_str(&name_to_string(symbol)),
+            Markup::Splice { expr, .. } => {
+                format_str.push_str("{}");
+                args.push(expr);
+            }
+            _ => unreachable!("dynamic attribute names only contain symbols and splices"),
+        }
+    }
+    quote!(format!(#format_str, #((#args)),*))
+}
+
+/// Returns the pattern and iterator of a `@for` loop, pairing each item
+/// with a `maud::Loop` if the loop has a `with <ident>` binding.
+fn for_head(
+    pattern: TokenStream,
+    iter: TokenStream,
+    meta: Option<Ident>,
+) -> (TokenStream, TokenStream) {
+    match meta {
+        Some(meta) => (
+            quote!((#pattern, #meta)),
+            quote!(maud::LoopExt::with_loop(::core::iter::IntoIterator::into_iter(#iter))),
+        ),
+        None => (pattern, iter),
+    }
+}
+
+/// Returns `true` if this is a boolean attribute, whose presence is all
+/// that matters.
+///
+/// See <https://html.spec.whatwg.org/multipage/indices.html#attributes-3>.
+fn is_boolean_attr(name: &str) -> bool {
+    matches!(
+        name,
+        "allowfullscreen"
+            | "async"
+            | "autofocus"
+            | "autoplay"
+            | "checked"
+            | "controls"
+            | "default"
+            | "defer"
+            | "disabled"
+            | "formnovalidate"
+            | "hidden"
+            | "inert"
+            | "ismap"
+            | "itemscope"
+            | "loop"
+            | "multiple"
+            | "muted"
+            | "nomodule"
+            | "novalidate"
+            | "open"
+            | "playsinline"
+            | "readonly"
+            | "required"
+            | "reversed"
+            | "selected"
+    )
+}
+
+fn desugar_ids(ids: Vec<Markup>) -> Option<NamedAttr> {
+    if ids.is_empty() {
+        return None;
+    }
+    let mut markups = Vec::new();
+    for id in ids {
+        if !markups.is_empty() {
+            markups.push(Markup::Literal {
+                content: " ".to_owned(),
+                span: id.span(),
+            });
+        }
+        markups.push(id);
+    }
+    Some(NamedAttr {
+        name: TokenStream::from(TokenTree::Ident(Ident::new("id", Span::call_site()))),
+        attr_type: AttrType::Normal {
+            value: Markup::Block(Block {
+                markups,
+                outer_span: SpanRange::call_site(),
+            }),
+        },
+    })
+}
+
 ////////////////////////////////////////////////////////
 
 struct Builder {
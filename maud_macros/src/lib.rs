@@ -6,16 +6,20 @@
 extern crate proc_macro;
 
 mod ast;
+mod entities;
 mod escape;
 mod generate;
 mod parse;
+mod raw;
+mod text;
 
-#[cfg(feature = "zephyr")]
+#[cfg(any(feature = "zephyr", feature = "zephyr_build"))]
 mod zephyr;
 
 use proc_macro2::{Ident, Span, TokenStream, TokenTree};
-use proc_macro_error::proc_macro_error;
+use proc_macro_error::{abort, abort_call_site, proc_macro_error};
 use quote::quote;
+use syn::LitStr;
 
 #[proc_macro]
 #[proc_macro_error]
@@ -31,29 +35,85 @@ pub fn html_debug(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     expr.into()
 }
 
+/// Returns a string literal of classes, registering them with zephyr when
+/// the `zephyr` or `zephyr_build` feature is enabled.
+#[proc_macro]
+#[proc_macro_error]
+pub fn class(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_class(input.into()).into()
+}
+
+/// Expands to the directory that classes are recorded in, for
+/// `maud::zephyr::classes_dir`.
+#[cfg(feature = "zephyr_build")]
+#[doc(hidden)]
+#[proc_macro]
+#[proc_macro_error]
+pub fn zephyr_classes_dir(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    zephyr::classes_dir_literal().into()
+}
+
 fn expand(input: TokenStream) -> TokenStream {
     let output_ident = TokenTree::Ident(Ident::new("__maud_output", Span::mixed_site()));
+    let buffer_ident = TokenTree::Ident(Ident::new("__maud_buffer", Span::mixed_site()));
     // Heuristic: the size of the resulting markup tends to correlate with the
     // code size of the template itself
     let size_hint = input.to_string().len();
     let markups = parse::parse(input);
 
+    #[cfg(any(feature = "zephyr", feature = "zephyr_build"))]
+    let classes = zephyr::classes(&markups);
+    #[cfg(feature = "zephyr_build")]
+    zephyr::record(&classes);
     #[cfg(feature = "zephyr")]
-    let zephyr_stmt = zephyr::zephyr(&markups);
+    let zephyr_stmt = zephyr::register(&classes);
     let stmts = generate::generate(markups, output_ident.clone());
 
+    // The generated code writes through a `&mut String`, so that the same
+    // statements can also be used inside lazy component bodies and local
+    // components, which receive the buffer as an argument
     #[cfg(feature = "zephyr")]
     return quote!({
-        let mut #output_ident = String::with_capacity(#size_hint);
+        let mut #buffer_ident = String::with_capacity(#size_hint);
+        let #output_ident = &mut #buffer_ident;
         #zephyr_stmt
         #stmts
-        maud::PreEscaped(#output_ident)
+        maud::PreEscaped(#buffer_ident)
     });
 
     #[cfg(not(feature = "zephyr"))]
     quote!({
-        let mut #output_ident = String::with_capacity(#size_hint);
+        let mut #buffer_ident = String::with_capacity(#size_hint);
+        let #output_ident = &mut #buffer_ident;
         #stmts
-        maud::PreEscaped(#output_ident)
+        maud::PreEscaped(#buffer_ident)
     })
 }
+
+fn expand_class(input: TokenStream) -> TokenStream {
+    if input.is_empty() {
+        abort_call_site!("expected a string literal of classes, like `class!(\"px-2 text-red\")`");
+    }
+    let classes = match syn::parse2::<LitStr>(input.clone()) {
+        Ok(classes) => classes,
+        Err(_) => abort!(
+            input,
+            "expected a string literal";
+            help = "write the classes as `class!(\"px-2 text-red\")`"
+        ),
+    };
+
+    // Register the classes in the same way as `html!`, so that they end up
+    // in the generated CSS
+    #[cfg(feature = "zephyr_build")]
+    zephyr::record(&classes.value());
+
+    #[cfg(feature = "zephyr")]
+    return quote!({
+        maud::zephyr::register_class!(#classes);
+        #classes
+    });
+
+    #[cfg(not(feature = "zephyr"))]
+    quote!(#classes)
+}
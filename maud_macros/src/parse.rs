@@ -1,21 +1,32 @@
 use peekaboo::*;
-use proc_macro2::{Delimiter, Ident, Literal, Spacing, Span, TokenStream, TokenTree};
+use proc_macro2::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
 use proc_macro_error::{abort, abort_call_site, emit_error, SpanRange};
-use quote::ToTokens;
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range};
 
 use syn::Lit;
 
-use crate::ast;
+use crate::{ast, entities, raw, text};
 
 pub fn parse(input: TokenStream) -> Vec<ast::Markup> {
     Parser::new(input).markups()
 }
 
+/// The name of an attribute, which may be only known at runtime.
+enum AttrName {
+    Static(TokenStream),
+    /// A name made up of literal parts and splices.
+    Dynamic(Vec<ast::Markup>),
+}
+
 #[derive(Clone)]
 struct Parser {
     /// If we're inside an attribute, then this contains the attribute name.
     current_attr: Option<String>,
+    /// Whether we're inside a `pre` or `textarea`, where `@text` blocks
+    /// are left as written.
+    preformatted: bool,
+    /// The names of components defined with `@fn` so far.
+    local_fns: Vec<String>,
     input: Peekable<<TokenStream as IntoIterator>::IntoIter, 2>,
 }
 
@@ -31,6 +42,8 @@ impl Parser {
     fn new(input: TokenStream) -> Parser {
         Parser {
             current_attr: None,
+            preformatted: false,
+            local_fns: Vec::new(),
             input: input.into_iter().peekable_n(),
         }
     }
@@ -38,6 +51,8 @@ impl Parser {
     fn with_input(&self, input: TokenStream) -> Parser {
         Parser {
             current_attr: self.current_attr.clone(),
+            preformatted: self.preformatted,
+            local_fns: self.local_fns.clone(),
             input: input.into_iter().peekable_n(),
         }
     }
@@ -54,6 +69,22 @@ impl Parser {
         peek1.map(|first| (first, peek2))
     }
 
+    /// Returns `true` if the next token is a parenthesized group.
+    fn peek_parens(&mut self) -> bool {
+        matches!(
+            self.peek(),
+            Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Parenthesis
+        )
+    }
+
+    /// Returns `true` if the next token is a braced group.
+    fn peek_braces(&mut self) -> bool {
+        matches!(
+            self.peek(),
+            Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Brace
+        )
+    }
+
     /// Advances the cursor by one step.
     fn advance(&mut self) {
         self.next();
@@ -80,6 +111,24 @@ impl Parser {
                     self.advance2();
                     result.push(self.let_expr(span, keyword));
                 }
+                Some((TokenTree::Punct(punct), Some(TokenTree::Ident(ident))))
+                    if punct.as_char() == '@' && *ident == "fn" =>
+                {
+                    let span = punct.span();
+                    self.advance2();
+                    result.push(self.local_fn(span));
+                }
+                Some((TokenTree::Punct(punct), Some(TokenTree::Group(group))))
+                    if punct.as_char() == '@' && group.delimiter() == Delimiter::Brace =>
+                {
+                    let markup = ast::Markup::Statements {
+                        at_span: SpanRange::single_span(punct.span()),
+                        stmts: group.stream(),
+                        stmts_span: SpanRange::single_span(group.span()),
+                    };
+                    self.advance2();
+                    result.push(markup);
+                }
                 _ => result.push(self.markup()),
             }
         }
@@ -124,10 +173,54 @@ impl Parser {
                                 };
                                 abort!(span, "`@let` only works inside a block");
                             }
-                            // TODO add components here
+                            "fn" => {
+                                let span = SpanRange {
+                                    first: at_span,
+                                    last: ident.span(),
+                                };
+                                abort!(span, "`@fn` only works inside a block");
+                            }
+                            "tag" if self.peek_parens() => self.dynamic_element(at_span),
+                            "comment" if self.peek_braces() => self.comment(at_span),
+                            "raw" if self.peek_braces() => self.raw(at_span),
+                            "format" if self.peek_braces() => self.format_string(at_span),
+                            "text" if self.peek_braces() => self.text(at_span),
+                            "sp" if !self.peek_parens() => ast::Markup::Literal {
+                                content: " ".to_owned(),
+                                span: SpanRange {
+                                    first: at_span,
+                                    last: ident.span(),
+                                },
+                            },
+                            "nl" if !self.peek_parens() => ast::Markup::Literal {
+                                content: "\n".to_owned(),
+                                span: SpanRange {
+                                    first: at_span,
+                                    last: ident.span(),
+                                },
+                            },
+                            name if self.local_fns.iter().any(|local| local == name)
+                                && self.peek_parens() =>
+                            {
+                                self.local_call(at_span, ident)
+                            }
                             _ => self.custom_component(at_span, keyword),
                         }
                     }
+                    // Component with an absolute or qualified path, e.g. `@::ui::button()`
+                    // or `@<T as Widget>::render()`
+                    Some(TokenTree::Punct(punct))
+                        if punct.as_char() == ':' || punct.as_char() == '<' =>
+                    {
+                        self.custom_component(at_span, TokenTree::Punct(punct))
+                    }
+                    Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                        let span = SpanRange {
+                            first: at_span,
+                            last: group.span(),
+                        };
+                        abort!(span, "`@{ ... }` only works inside a block");
+                    }
                     _ => {
                         abort!(at_span, "expected keyword after `@`");
                     }
@@ -167,6 +260,12 @@ impl Parser {
                 let name = self.try_namespaced_name().expect("identifier");
                 self.element(name)
             }
+            // Character reference
+            TokenTree::Punct(ref punct) if punct.as_char() == '&' => {
+                let amp_span = punct.span();
+                self.advance();
+                self.entity(amp_span)
+            }
             // Div element shorthand
             TokenTree::Punct(ref punct) if punct.as_char() == '.' || punct.as_char() == '#' => {
                 let name = TokenTree::Ident(Ident::new("div", punct.span()));
@@ -224,6 +323,174 @@ impl Parser {
         }
     }
 
+    /// Parses a character reference, like `&mdash;`, `&#169;` or `&#x2014;`.
+    ///
+    /// The leading `&` should already be consumed.
+    fn entity(&mut self, amp_span: Span) -> ast::Markup {
+        let (entity, last_span) = match self.next() {
+            Some(TokenTree::Ident(name)) => {
+                let name_string = name.to_string();
+                if !entities::is_named(&name_string) {
+                    let suggestion = entities::NAMES
+                        .iter()
+                        .find(|known| known.eq_ignore_ascii_case(&name_string));
+                    match suggestion {
+                        Some(suggestion) => emit_error!(
+                            name,
+                            "unknown character reference `&{};`", name_string;
+                            help = "character references are case-sensitive: `&{};`", suggestion
+                        ),
+                        None => {
+                            emit_error!(name, "unknown character reference `&{};`", name_string)
+                        }
+                    }
+                }
+                (format!("&{name_string};"), name.span())
+            }
+            Some(TokenTree::Punct(ref hash)) if hash.as_char() == '#' => {
+                let number = match self.next() {
+                    Some(token @ (TokenTree::Literal(_) | TokenTree::Ident(_))) => token,
+                    _ => abort!(hash, "expected a number after `&#`"),
+                };
+                let number_string = number.to_string();
+                let code_point = match number_string.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => number_string.parse(),
+                };
+                match code_point.ok().and_then(char::from_u32) {
+                    Some(c) if c != '\0' => {}
+                    _ => emit_error!(number, "invalid character reference `&#{};`", number_string),
+                }
+                (format!("&#{number_string};"), number.span())
+            }
+            _ => abort!(
+                amp_span,
+                "expected character reference after `&`";
+                help = "to write a literal `&`, put it in a string: `\"&\"`"
+            ),
+        };
+        match self.next() {
+            Some(TokenTree::Punct(ref semi)) if semi.as_char() == ';' => ast::Markup::Entity {
+                entity,
+                span: SpanRange {
+                    first: amp_span,
+                    last: semi.span(),
+                },
+            },
+            _ => abort!(
+                last_span,
+                "expected `;` after character reference";
+                help = "write it as `{}`", entity
+            ),
+        }
+    }
+
+    /// Parses a `@format { "..." }` string, splitting it into literals and
+    /// splices.
+    ///
+    /// The leading `@format` should already be consumed.
+    fn format_string(&mut self, at_span: Span) -> ast::Markup {
+        let group = match self.next() {
+            Some(TokenTree::Group(group)) => group,
+            _ => unreachable!(),
+        };
+        let mut tokens = group.stream().into_iter();
+        let (literal, value) = match (tokens.next(), tokens.next()) {
+            (Some(TokenTree::Literal(literal)), None) => match Lit::new(literal.clone()) {
+                Lit::Str(lit_str) => (literal, lit_str.value()),
+                _ => abort!(literal, "expected a string literal"),
+            },
+            _ => abort!(
+                group,
+                "`@format` takes a single string literal";
+                help = "write it like `@format { \"Hello, {name}!\" }`"
+            ),
+        };
+        let literals = [(0, literal.clone())];
+        let span_of =
+            |range: Range<usize>| literal_span(&literals, range).unwrap_or_else(|| literal.span());
+        let mut markups = Vec::new();
+        let mut text = String::new();
+        let mut chars = value.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '{' if matches!(chars.peek(), Some((_, '{'))) => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if matches!(chars.peek(), Some((_, '}'))) => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let end = match value[index..].find('}') {
+                        Some(end) => index + end,
+                        None => {
+                            emit_error!(span_of(index..index + 1), "unmatched `{` in format string";
+                                help = "to write a literal `{`, double it: `{{`");
+                            break;
+                        }
+                    };
+                    while matches!(chars.peek(), Some(&(i, _)) if i <= end) {
+                        chars.next();
+                    }
+                    if !text.is_empty() {
+                        markups.push(ast::Markup::Literal {
+                            content: std::mem::take(&mut text),
+                            span: SpanRange::single_span(literal.span()),
+                        });
+                    }
+                    let capture = &value[index + 1..end];
+                    let (expr, spec) = match capture.split_once(':') {
+                        Some((expr, spec)) => (expr, Some(spec)),
+                        None => (capture, None),
+                    };
+                    let expr_range = index + 1..index + 1 + expr.len();
+                    let expr = match format_capture(expr, span_of(expr_range.clone())) {
+                        Some(expr) => expr,
+                        None => {
+                            emit_error!(
+                                span_of(expr_range),
+                                "expected a name in format string, found `{{{}}}`", capture;
+                                help = "captures can be a variable or a field, like `{name}` or `{user.name}`"
+                            );
+                            continue;
+                        }
+                    };
+                    let expr = match spec {
+                        Some(spec) => {
+                            let format_str = format!("{{:{spec}}}");
+                            quote::quote!(::core::format_args!(#format_str, #expr))
+                        }
+                        None => expr,
+                    };
+                    markups.push(ast::Markup::Splice {
+                        expr,
+                        outer_span: SpanRange::single_span(span_of(index..end + 1)),
+                    });
+                }
+                '}' => {
+                    emit_error!(span_of(index..index + 1), "unmatched `}` in format string";
+                        help = "to write a literal `}`, double it: `}}`");
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            markups.push(ast::Markup::Literal {
+                content: text,
+                span: SpanRange::single_span(literal.span()),
+            });
+        }
+        ast::Markup::Block(ast::Block {
+            markups,
+            outer_span: SpanRange {
+                first: at_span,
+                last: group.span(),
+            },
+        })
+    }
+
     /// Parses an `@if` expression.
     ///
     /// The leading `@if` should already be consumed.
@@ -331,14 +598,11 @@ impl Parser {
     /// The leading `@for` should already be consumed.
     fn for_expr(&mut self, at_span: Span, keyword: TokenTree) -> ast::Markup {
         let keyword_span = keyword.span();
-        let mut head = vec![keyword];
+        let mut pattern = Vec::new();
         loop {
             match self.next() {
-                Some(TokenTree::Ident(ref in_keyword)) if *in_keyword == "in" => {
-                    head.push(TokenTree::Ident(in_keyword.clone()));
-                    break;
-                }
-                Some(token) => head.push(token),
+                Some(TokenTree::Ident(ref in_keyword)) if *in_keyword == "in" => break,
+                Some(token) => pattern.push(token),
                 None => {
                     let span = SpanRange {
                         first: at_span,
@@ -348,12 +612,13 @@ impl Parser {
                 }
             }
         }
+        let mut iter = Vec::new();
         let body = loop {
             match self.next() {
                 Some(TokenTree::Group(ref block)) if block.delimiter() == Delimiter::Brace => {
                     break self.block(block.stream(), SpanRange::single_span(block.span()));
                 }
-                Some(token) => head.push(token),
+                Some(token) => iter.push(token),
                 None => {
                     let span = SpanRange {
                         first: at_span,
@@ -363,67 +628,461 @@ impl Parser {
                 }
             }
         };
-        ast::Markup::Special {
-            segments: vec![ast::Special {
-                at_span: SpanRange::single_span(at_span),
-                head: head.into_iter().collect(),
-                body,
-            }],
+        let meta = Self::loop_meta(&mut iter);
+        let mut separator = None;
+        let mut empty = None;
+        loop {
+            let slot = if self.peek_clause("separator") {
+                &mut separator
+            } else if self.peek_clause("empty") {
+                &mut empty
+            } else {
+                break;
+            };
+            let (clause_span, block) = self.clause();
+            if slot.is_some() {
+                abort!(clause_span, "duplicate clause in `@for` loop");
+            }
+            *slot = Some(block);
+        }
+        ast::Markup::For {
+            at_span: SpanRange::single_span(at_span),
+            pattern: pattern.into_iter().collect(),
+            iter: iter.into_iter().collect(),
+            meta,
+            body,
+            separator,
+            empty,
         }
     }
 
-    fn custom_component(&mut self, at_span: Span, name: TokenTree) -> ast::Markup {
-        let at_span = SpanRange::single_span(at_span);
+    /// Splits a trailing `with <ident>` off the iterator of a `@for` loop.
+    ///
+    /// This can't be confused with the iterator expression itself, since
+    /// `with` is never followed by another identifier in valid Rust.
+    fn loop_meta(iter: &mut Vec<TokenTree>) -> Option<Ident> {
+        match iter.as_slice() {
+            [.., TokenTree::Ident(with), TokenTree::Ident(_)]
+                if iter.len() > 2 && *with == "with" => {}
+            _ => return None,
+        }
+        let meta = match iter.pop() {
+            Some(TokenTree::Ident(ident)) => ident,
+            _ => unreachable!(),
+        };
+        iter.pop();
+        if meta == "loop" {
+            abort!(
+                meta,
+                "`loop` is a keyword, so it can't name the loop metadata";
+                help = "try another name, like `with meta`"
+            );
+        }
+        Some(meta)
+    }
 
-        let group = if let Some(TokenTree::Group(group)) = self.next() {
-            if group.delimiter() == Delimiter::Parenthesis {
-                group
-            } else {
-                abort!(at_span, "expected parentesised group");
+    /// Returns `true` if the upcoming tokens are a clause such as
+    /// `@empty { ... }`, as opposed to e.g. a component called `empty`.
+    fn peek_clause(&self, keyword: &str) -> bool {
+        let mut fork = self.clone();
+        matches!(fork.next(), Some(TokenTree::Punct(ref punct)) if punct.as_char() == '@')
+            && matches!(fork.next(), Some(TokenTree::Ident(ref ident)) if *ident == keyword)
+            && matches!(
+                fork.next(),
+                Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Brace
+            )
+    }
+
+    /// Parses a clause that was found by `peek_clause`, returning the span of
+    /// its `@keyword` along with its body.
+    fn clause(&mut self) -> (SpanRange, ast::Block) {
+        let (at_span, keyword_span) = match (self.next(), self.next()) {
+            (Some(at), Some(keyword)) => (at.span(), keyword.span()),
+            _ => unreachable!(),
+        };
+        let block = match self.next() {
+            Some(TokenTree::Group(group)) => {
+                self.block(group.stream(), SpanRange::single_span(group.span()))
             }
-        } else {
-            abort!(at_span, "expected parentesised group");
+            _ => unreachable!(),
+        };
+        let span = SpanRange {
+            first: at_span,
+            last: keyword_span,
         };
-        let expr = group.stream();
+        (span, block)
+    }
 
-        let body = match self.peek() {
-            Some(TokenTree::Punct(ref punct))
-                if punct.as_char() == ';' || punct.as_char() == '/' =>
-            {
-                // Void element
-                let c = punct.as_char();
-                let semi_span = SpanRange::single_span(punct.span());
-                if c == '/' {
-                    emit_error!(
-                        punct,
-                        "void elements must use `;`, not `/`";
-                        help = "change this to `;`";
-                        help = "see https://github.com/lambda-fairy/maud/pull/315 for details";
-                    );
-                }
-                self.advance();
-                ast::ElementBody::Void { semi_span }
+    /// Parses a `@comment` block.
+    ///
+    /// The leading `@comment` should already be consumed.
+    fn comment(&mut self, at_span: Span) -> ast::Markup {
+        let body = match self.next() {
+            Some(TokenTree::Group(ref group)) => {
+                self.block(group.stream(), SpanRange::single_span(group.span()))
             }
-            Some(_) => match self.markup() {
-                ast::Markup::Block(block) => ast::ElementBody::Block { block },
-                markup => {
-                    let markup_span = markup.span();
-                    abort!(
-                        markup_span,
-                        "element body must be wrapped in braces";
-                        help = "see https://github.com/lambda-fairy/maud/pull/137 for details"
-                    );
-                }
+            _ => unreachable!(),
+        };
+        // Adjacent literals end up next to each other in the output, so they
+        // are checked as one run. Splices are escaped when they're rendered.
+        let mut runs: Vec<(String, SpanRange, bool)> = Vec::new();
+        let mut in_run = false;
+        for (index, markup) in body.markups.iter().enumerate() {
+            match markup {
+                ast::Markup::Literal { content, span } => match runs.last_mut() {
+                    Some((text, run_span, _)) if in_run => {
+                        text.push_str(content);
+                        run_span.last = span.last;
+                    }
+                    _ => {
+                        runs.push((content.clone(), *span, index == 0));
+                        in_run = true;
+                    }
+                },
+                ast::Markup::Splice { .. } | ast::Markup::ParseError { .. } => in_run = false,
+                markup => emit_error!(
+                    markup.span(),
+                    "`@comment` can only contain string literals and splices"
+                ),
+            }
+        }
+        let last_run = runs.len().checked_sub(1).filter(|_| in_run);
+        for (index, (text, span, at_start)) in runs.iter().enumerate() {
+            // See https://html.spec.whatwg.org/multipage/syntax.html#comments
+            if text.contains("--") {
+                emit_error!(*span, "comments can't contain `--`");
+            } else if *at_start && (text.starts_with('>') || text.starts_with("->")) {
+                emit_error!(*span, "comments can't start with `>` or `->`");
+            } else if last_run == Some(index) && text.ends_with("<!-") {
+                emit_error!(*span, "comments can't end with `<!-`");
+            }
+        }
+        for pair in runs.windows(2) {
+            let ((before, before_span, _), (after, after_span, _)) = (&pair[0], &pair[1]);
+            if before.ends_with('-') && after.starts_with('-') {
+                let span = SpanRange {
+                    first: before_span.last,
+                    last: after_span.first,
+                };
+                emit_error!(
+                    span,
+                    "comments can't contain `--`";
+                    help = "the splices in between might render nothing"
+                );
+            }
+        }
+        ast::Markup::Comment {
+            at_span: SpanRange::single_span(at_span),
+            body,
+        }
+    }
+
+    /// Parses a `@text` block, which is dedented and optionally reflowed.
+    ///
+    /// The leading `@text` should already be consumed.
+    fn text(&mut self, at_span: Span) -> ast::Markup {
+        let group = match self.next() {
+            Some(TokenTree::Group(group)) => group,
+            _ => unreachable!(),
+        };
+        let mut tokens = group.stream().into_iter().peekable();
+        // Options come first, e.g. `@text { reflow "..." }`
+        let mut reflow = false;
+        while let Some(TokenTree::Ident(ident)) = tokens.peek() {
+            if ident == "reflow" {
+                reflow = true;
+            } else {
+                emit_error!(
+                    ident,
+                    "unknown `@text` option";
+                    help = "the only option is `@text { reflow \"...\" }`"
+                );
+            }
+            tokens.next();
+        }
+        let mut content = String::new();
+        for token in tokens {
+            match token {
+                TokenTree::Literal(literal) => match Lit::new(literal.clone()) {
+                    Lit::Str(lit_str) => content.push_str(&lit_str.value()),
+                    _ => emit_error!(literal, "expected string literal"),
+                },
+                token => emit_error!(token, "`@text` can only contain string literals"),
+            }
+        }
+        if !self.preformatted {
+            content = text::dedent(&content);
+            if reflow {
+                content = text::reflow(&content);
+            }
+        }
+        ast::Markup::Literal {
+            content,
+            span: SpanRange {
+                first: at_span,
+                last: group.span(),
             },
-            None => abort_call_site!("expected `;`, found end of macro"),
+        }
+    }
+
+    /// Parses a `@raw` block, checking that its contents are well-formed.
+    ///
+    /// The leading `@raw` should already be consumed.
+    fn raw(&mut self, at_span: Span) -> ast::Markup {
+        let group = match self.next() {
+            Some(TokenTree::Group(group)) => group,
+            _ => unreachable!(),
         };
+        let mut content = String::new();
+        // The literals that make up `content`, along with where they start
+        let mut literals = Vec::new();
+        for token in group.stream() {
+            match token {
+                TokenTree::Literal(literal) => match Lit::new(literal.clone()) {
+                    Lit::Str(lit_str) => {
+                        literals.push((content.len(), literal));
+                        content.push_str(&lit_str.value());
+                    }
+                    _ => emit_error!(literal, "expected string literal"),
+                },
+                token => emit_error!(
+                    token,
+                    "`@raw` can only contain string literals";
+                    help = "use `(maud::PreEscaped(...))` to include HTML that isn't known at compile time"
+                ),
+            }
+        }
+        if let Err((range, message)) = raw::check(&content) {
+            let span = literal_span(&literals, range).unwrap_or_else(|| group.span());
+            emit_error!(span, "invalid HTML in `@raw` block: {}", message);
+        }
+        ast::Markup::Raw {
+            at_span: SpanRange::single_span(at_span),
+            content,
+            body_span: SpanRange::single_span(group.span()),
+        }
+    }
+
+    /// Parses a local component definition, `@fn name(params) { ... }`.
+    ///
+    /// The leading `@fn` should already be consumed.
+    fn local_fn(&mut self, at_span: Span) -> ast::Markup {
+        let name = match self.next() {
+            Some(TokenTree::Ident(name)) => name,
+            Some(token) => abort!(token, "expected a name after `@fn`"),
+            None => abort_call_site!("expected a name after `@fn`, found end of macro"),
+        };
+        let params = match self.next() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                group.stream()
+            }
+            Some(token) => abort!(token, "expected parameters after `@fn {}`", name),
+            None => abort_call_site!(
+                "expected parameters after `@fn {}`, found end of macro",
+                name
+            ),
+        };
+        // Register the name before parsing the body, so that it can call
+        // itself
+        self.local_fns.push(name.to_string());
+        let body = match self.next() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                self.block(group.stream(), SpanRange::single_span(group.span()))
+            }
+            Some(token) => abort!(token, "expected body after `@fn {}(...)`", name),
+            None => abort_call_site!(
+                "expected body after `@fn {}(...)`, found end of macro",
+                name
+            ),
+        };
+        ast::Markup::LocalFn {
+            at_span: SpanRange::single_span(at_span),
+            name,
+            params,
+            body,
+        }
+    }
+
+    /// Parses a call to a component defined with `@fn`.
+    ///
+    /// The leading `@` and the name should already be consumed.
+    fn local_call(&mut self, at_span: Span, name: Ident) -> ast::Markup {
+        let args = match self.next() {
+            Some(TokenTree::Group(group)) => group.stream(),
+            _ => unreachable!(),
+        };
+        for attr in self.attrs() {
+            emit_error!(
+                attr.span(),
+                "components defined with `@fn` can't take attributes";
+                help = "add a parameter to `{}` instead", name
+            );
+        }
+        let lazy = self.lazy_marker();
+        let body = self.element_body();
+        ast::Markup::LocalCall {
+            at_span: SpanRange::single_span(at_span),
+            name,
+            args,
+            body,
+            lazy,
+        }
+    }
+
+    /// Parses a custom component invocation.
+    ///
+    /// The leading `@` should already be consumed. `first` is the first
+    /// token of the component's path, which has been consumed as well.
+    fn custom_component(&mut self, at_span: Span, first: TokenTree) -> ast::Markup {
+        let at_span = SpanRange::single_span(at_span);
+        let (name, expr) = self.component_head(at_span, first);
+        // A bare `..` asks for the attributes to be passed even if there
+        // are none, e.g. `@button("Save")..;`
+        let pass_attrs = self.bare_dots();
+        let attrs = self.attrs();
+        let attrs = if pass_attrs || !attrs.is_empty() {
+            Some(attrs)
+        } else {
+            None
+        };
+
+        let lazy = self.lazy_marker();
+        let body = self.element_body();
 
         ast::Markup::Custom {
             at_span,
-            name: name.into_token_stream(),
+            name,
             expr,
+            attrs,
             body,
+            lazy,
+        }
+    }
+
+    /// Parses the head of a component invocation, i.e. everything up to and
+    /// including its argument list.
+    ///
+    /// The head may be a path with generics (`ui::list::<User>`), or a
+    /// method call on some receiver (`self.header`). Returns the path and
+    /// the contents of the argument list.
+    fn component_head(
+        &mut self,
+        at_span: SpanRange,
+        first: TokenTree,
+    ) -> (TokenStream, TokenStream) {
+        let mut name = Vec::new();
+        let mut angle_depth = 0usize;
+        let mut next = Some(first);
+        loop {
+            let token = match next.take().or_else(|| self.next()) {
+                Some(token) => token,
+                None => {
+                    let mut span = ast::span_tokens(name);
+                    span.first = at_span.first;
+                    abort!(span, "expected argument list for this component");
+                }
+            };
+            match token {
+                TokenTree::Group(ref group)
+                    if group.delimiter() == Delimiter::Parenthesis && angle_depth == 0 =>
+                {
+                    if self.is_method_call_next() {
+                        // A call in the middle of a method chain, e.g. `@self.items().list()`
+                        name.push(token.clone());
+                    } else {
+                        return (name.into_iter().collect(), group.stream());
+                    }
+                }
+                TokenTree::Punct(ref punct) if punct.as_char() == '<' => {
+                    angle_depth += 1;
+                    name.push(token.clone());
+                }
+                TokenTree::Punct(ref punct) if punct.as_char() == '>' => {
+                    // Don't mistake the `>` in `->` for a closing bracket
+                    let is_arrow = matches!(
+                        name.last(),
+                        Some(TokenTree::Punct(prev))
+                            if prev.as_char() == '-' && prev.spacing() == Spacing::Joint
+                    );
+                    if !is_arrow {
+                        angle_depth = match angle_depth.checked_sub(1) {
+                            Some(depth) => depth,
+                            None => abort!(punct, "unbalanced `>` in component name"),
+                        };
+                    }
+                    name.push(token.clone());
+                }
+                _ if angle_depth > 0 => name.push(token),
+                TokenTree::Ident(_) => name.push(token),
+                TokenTree::Punct(ref punct) if punct.as_char() == ':' || punct.as_char() == '.' => {
+                    name.push(token.clone());
+                }
+                _ => {
+                    abort!(
+                        token,
+                        "expected argument list for this component";
+                        help = "components are called like functions, e.g. `@my_component()`"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Consumes a `..` that isn't followed by a parenthesized expression,
+    /// returning `true` if there was one.
+    fn bare_dots(&mut self) -> bool {
+        let mut fork = self.clone();
+        let is_bare = matches!(fork.next(), Some(TokenTree::Punct(ref punct)) if punct.as_char() == '.')
+            && matches!(fork.next(), Some(TokenTree::Punct(ref punct)) if punct.as_char() == '.')
+            && !matches!(
+                fork.next(),
+                Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Parenthesis
+            );
+        if is_bare {
+            self.advance2();
         }
+        is_bare
+    }
+
+    /// Consumes the `||` before a component body that should be passed
+    /// lazily, e.g. `@card() || { ... }`, returning `true` if there was one.
+    fn lazy_marker(&mut self) -> bool {
+        let mut fork = self.clone();
+        let first = match fork.next() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '|' => punct,
+            _ => return false,
+        };
+        if !matches!(fork.next(), Some(TokenTree::Punct(ref punct)) if punct.as_char() == '|') {
+            abort!(first, "expected `||` before a lazy component body");
+        }
+        match fork.peek() {
+            Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Brace => {}
+            _ => abort!(
+                first,
+                "expected a body after `||`";
+                help = "a lazy body is written like a closure, e.g. `@card() || { ... }`"
+            ),
+        }
+        self.advance2();
+        true
+    }
+
+    /// Returns `true` if the upcoming tokens look like a method call, e.g.
+    /// `.list()` or `.list::<T>()`, rather than a class shorthand like
+    /// `.hover:(underline)`.
+    fn is_method_call_next(&self) -> bool {
+        let mut fork = self.clone();
+        matches!(fork.next(), Some(TokenTree::Punct(ref punct)) if punct.as_char() == '.')
+            && matches!(fork.next(), Some(TokenTree::Ident(_)))
+            && match fork.next() {
+                Some(TokenTree::Group(ref group)) => group.delimiter() == Delimiter::Parenthesis,
+                Some(TokenTree::Punct(ref punct))
+                    if punct.as_char() == ':' && punct.spacing() == Spacing::Joint =>
+                {
+                    matches!(fork.next(), Some(TokenTree::Punct(ref punct)) if punct.as_char() == ':')
+                }
+                _ => false,
+            }
     }
 
     /// Parses a `@match` expression.
@@ -559,6 +1218,22 @@ impl Parser {
                         tokens.push(token.clone());
                         break;
                     }
+                    // The initializer of a let-else can't end with a `}`, so
+                    // this isn't the `else` of an `if` expression
+                    TokenTree::Ident(ref ident)
+                        if *ident == "else"
+                            && !matches!(
+                                tokens.last(),
+                                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace
+                            ) =>
+                    {
+                        let else_body = self.let_else_body(at_span, ident);
+                        return ast::Markup::Let {
+                            at_span: SpanRange::single_span(at_span),
+                            tokens: tokens.into_iter().collect(),
+                            else_body: Some(else_body),
+                        };
+                    }
                     _ => tokens.push(token),
                 },
                 None => {
@@ -575,9 +1250,35 @@ impl Parser {
         ast::Markup::Let {
             at_span: SpanRange::single_span(at_span),
             tokens: tokens.into_iter().collect(),
+            else_body: None,
         }
     }
 
+    /// Parses the `else { ... };` of a `@let` expression.
+    ///
+    /// The `else` should already be consumed.
+    fn let_else_body(&mut self, at_span: Span, else_keyword: &Ident) -> ast::Block {
+        let span = SpanRange {
+            first: at_span,
+            last: else_keyword.span(),
+        };
+        let body = match self.next() {
+            Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Brace => {
+                self.block(group.stream(), SpanRange::single_span(group.span()))
+            }
+            _ => abort!(span, "expected body for this `@let ... else`"),
+        };
+        match self.next() {
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == ';' => {}
+            _ => abort!(
+                span.join_range(body.span()),
+                "unexpected end of `@let` expression";
+                help = "are you missing a semicolon?"
+            ),
+        }
+        body
+    }
+
     /// Parses an element node.
     ///
     /// The element name should already be consumed.
@@ -587,7 +1288,43 @@ impl Parser {
             abort!(span, "unexpected element");
         }
         let attrs = self.attrs();
-        let body = match self.peek() {
+        let preformatted = self.preformatted;
+        if matches!(
+            ast::name_to_string(name.clone()).as_str(),
+            "pre" | "textarea"
+        ) {
+            self.preformatted = true;
+        }
+        let body = self.element_body();
+        self.preformatted = preformatted;
+        ast::Markup::Element { name, attrs, body }
+    }
+
+    /// Parses an element whose name is given by an expression.
+    ///
+    /// The leading `@tag` should already be consumed.
+    fn dynamic_element(&mut self, at_span: Span) -> ast::Markup {
+        let at_span = SpanRange::single_span(at_span);
+        if self.current_attr.is_some() {
+            abort!(at_span, "unexpected element");
+        }
+        let expr = match self.next() {
+            Some(TokenTree::Group(group)) => group.stream(),
+            _ => unreachable!("`@tag` should be followed by parentheses"),
+        };
+        let attrs = self.attrs();
+        let body = self.element_body();
+        ast::Markup::DynamicElement {
+            at_span,
+            expr,
+            attrs,
+            body,
+        }
+    }
+
+    /// Parses the body of an element, which is either a `;` or a block.
+    fn element_body(&mut self) -> ast::ElementBody {
+        match self.peek() {
             Some(TokenTree::Punct(ref punct))
                 if punct.as_char() == ';' || punct.as_char() == '/' =>
             {
@@ -617,64 +1354,111 @@ impl Parser {
                 }
             },
             None => abort_call_site!("expected `;`, found end of macro"),
-        };
-        ast::Markup::Element { name, attrs, body }
+        }
     }
 
     /// Parses the attributes of an element.
     fn attrs(&mut self) -> Vec<ast::Attr> {
         let mut attrs = Vec::new();
         loop {
-            if let Some(name) = self.try_namespaced_name() {
+            if let Some(name) = self.try_attr_name() {
                 // Attribute
-                match self.peek() {
-                    // Non-empty attribute
-                    Some(TokenTree::Punct(ref punct)) if punct.as_char() == '=' => {
-                        self.advance();
-                        // Parse a value under an attribute context
-                        assert!(self.current_attr.is_none());
-                        self.current_attr = Some(ast::name_to_string(name.clone()));
-                        let attr_type = match self.attr_toggler() {
-                            Some(toggler) => ast::AttrType::Optional { toggler },
-                            None => {
-                                let value = self.markup();
-                                ast::AttrType::Normal { value }
-                            }
-                        };
-                        self.current_attr = None;
-                        attrs.push(ast::Attr::Named {
+                let attr = match name {
+                    AttrName::Static(name) => {
+                        let attr_type = self.attr_type(ast::name_to_string(name.clone()));
+                        ast::Attr::Named {
                             named_attr: ast::NamedAttr { name, attr_type },
-                        });
-                    }
-                    // Empty attribute (legacy syntax)
-                    Some(TokenTree::Punct(ref punct)) if punct.as_char() == '?' => {
-                        self.advance();
-                        let toggler = self.attr_toggler();
-                        attrs.push(ast::Attr::Named {
-                            named_attr: ast::NamedAttr {
-                                name: name.clone(),
-                                attr_type: ast::AttrType::Empty { toggler },
-                            },
-                        });
+                        }
                     }
-                    // Empty attribute (new syntax)
-                    _ => {
-                        let toggler = self.attr_toggler();
-                        attrs.push(ast::Attr::Named {
-                            named_attr: ast::NamedAttr {
-                                name: name.clone(),
-                                attr_type: ast::AttrType::Empty { toggler },
-                            },
-                        });
+                    AttrName::Dynamic(name) => {
+                        let name_string = name
+                            .iter()
+                            .map(|part| match part {
+                                ast::Markup::Splice { expr, .. } => format!("({expr})"),
+                                ast::Markup::Symbol { symbol } => {
+                                    ast::name_to_string(symbol.clone())
+                                }
+                                _ => unreachable!(),
+                            })
+                            .collect();
+                        let attr_type = self.attr_type(name_string);
+                        ast::Attr::Dynamic {
+                            dynamic_attr: ast::DynamicAttr { name, attr_type },
+                        }
                     }
-                }
+                };
+                attrs.push(attr);
             } else {
-                match self.peek() {
+                match self.peek2() {
+                    // Attribute spread
+                    Some((TokenTree::Punct(ref dot1), Some(TokenTree::Punct(ref dot2))))
+                        if dot1.as_char() == '.' && dot2.as_char() == '.' =>
+                    {
+                        let dots_span = SpanRange {
+                            first: dot1.span(),
+                            last: dot2.span(),
+                        };
+                        self.advance2();
+                        match self.next() {
+                            Some(TokenTree::Group(ref group))
+                                if group.delimiter() == Delimiter::Parenthesis =>
+                            {
+                                attrs.push(ast::Attr::Spread {
+                                    dots_span,
+                                    expr: group.stream(),
+                                    expr_span: SpanRange::single_span(group.span()),
+                                });
+                            }
+                            _ => {
+                                abort!(
+                                    dots_span,
+                                    "expected parenthesized expression after `..`";
+                                    help = "write the attributes to spread as `..(attrs)`"
+                                );
+                            }
+                        }
+                    }
+                    // Utility class list, e.g. `.{m[1rem] hover:bg-red}`
+                    Some((TokenTree::Punct(ref punct), Some(TokenTree::Group(ref group))))
+                        if punct.as_char() == '.' && is_utility_class_list(group) =>
+                    {
+                        let dot_span = SpanRange::single_span(punct.span());
+                        let group = group.clone();
+                        self.advance2();
+                        for name in utility_classes(&group) {
+                            attrs.push(ast::Attr::Class {
+                                dot_span,
+                                name,
+                                toggler: None,
+                            });
+                        }
+                        self.reject_class_list_toggler();
+                    }
                     // Class shorthand
-                    Some(TokenTree::Punct(ref punct)) if punct.as_char() == '.' => {
+                    Some((TokenTree::Punct(ref punct), _)) if punct.as_char() == '.' => {
                         let dot_span = SpanRange::single_span(punct.span());
                         self.advance();
                         let name = self.class_or_id_name();
+                        // Variant group, e.g. `.hover:(text-red underline)`
+                        if let ast::Markup::Symbol { ref symbol } = name {
+                            if let Some((prefix, group)) = self.variant_group(symbol) {
+                                for class in utility_classes(&group) {
+                                    let ast::Markup::Literal { content, span } = class else {
+                                        unreachable!();
+                                    };
+                                    attrs.push(ast::Attr::Class {
+                                        dot_span,
+                                        name: ast::Markup::Literal {
+                                            content: format!("{prefix}:{content}"),
+                                            span,
+                                        },
+                                        toggler: None,
+                                    });
+                                }
+                                self.reject_class_list_toggler();
+                                continue;
+                            }
+                        }
                         let toggler = self.attr_toggler();
                         attrs.push(ast::Attr::Class {
                             dot_span,
@@ -682,12 +1466,43 @@ impl Parser {
                             toggler,
                         });
                     }
+                    // Optional ID shorthand
+                    Some((TokenTree::Punct(punct), Some(TokenTree::Group(group))))
+                        if punct.as_char() == '#' && group.delimiter() == Delimiter::Bracket =>
+                    {
+                        let hash_span = SpanRange::single_span(punct.span());
+                        let toggler = ast::Toggler {
+                            cond: group.stream(),
+                            cond_span: SpanRange::single_span(group.span()),
+                        };
+                        self.advance2();
+                        attrs.push(ast::Attr::OptionalId { hash_span, toggler });
+                    }
                     // ID shorthand
-                    Some(TokenTree::Punct(punct)) if punct.as_char() == '#' => {
+                    Some((TokenTree::Punct(punct), _)) if punct.as_char() == '#' => {
                         let hash_span = SpanRange::single_span(punct.span());
                         self.advance();
                         let name = self.class_or_id_name();
-                        attrs.push(ast::Attr::Id { hash_span, name });
+                        let toggler = self.attr_toggler();
+                        attrs.push(ast::Attr::Id {
+                            hash_span,
+                            name,
+                            toggler,
+                        });
+                    }
+                    // Attributes chosen by `@if` or `@match`
+                    Some((TokenTree::Punct(punct), Some(TokenTree::Ident(keyword))))
+                        if punct.as_char() == '@' && (*keyword == "if" || *keyword == "match") =>
+                    {
+                        let at_span = punct.span();
+                        let keyword = keyword.clone();
+                        self.advance2();
+                        let attr = if keyword == "if" {
+                            self.attr_if(at_span, keyword)
+                        } else {
+                            self.attr_match(at_span, keyword)
+                        };
+                        attrs.push(attr);
                     }
                     // If it's not a valid attribute, backtrack and bail out
                     _ => break,
@@ -696,24 +1511,39 @@ impl Parser {
         }
 
         let mut attr_map: HashMap<String, Vec<SpanRange>> = HashMap::new();
-        let mut has_class = false;
+        let mut conditional_id = None;
+        let mut named_id = None;
         for attr in &attrs {
             let name = match attr {
-                ast::Attr::Class { .. } => {
-                    if has_class {
-                        // Only check the first class to avoid spurious duplicates
-                        continue;
-                    }
-                    has_class = true;
-                    "class".to_string()
-                }
-                ast::Attr::Id { .. } => "id".to_string(),
-                ast::Attr::Named { named_attr } => named_attr
-                    .name
-                    .clone()
-                    .into_iter()
-                    .map(|token| token.to_string())
-                    .collect(),
+                // Classes are merged into a single attribute
+                ast::Attr::Class { .. } => continue,
+                ast::Attr::Id { toggler: None, .. } => "id".to_string(),
+                // Any number of conditional ids can be given, since at most
+                // one of them is rendered
+                ast::Attr::Id { .. } | ast::Attr::OptionalId { .. } => {
+                    conditional_id.get_or_insert(attr.span());
+                    continue;
+                }
+                // Spread and dynamic attributes are only known at runtime
+                ast::Attr::Spread { .. } | ast::Attr::Dynamic { .. } => continue,
+                // Checked against everything else below
+                ast::Attr::Branches { .. } => continue,
+                ast::Attr::Named { named_attr } => {
+                    let name: String = named_attr
+                        .name
+                        .clone()
+                        .into_iter()
+                        .map(|token| token.to_string())
+                        .collect();
+                    match name.as_str() {
+                        "id" => named_id = Some(attr.span()),
+                        // These are merged with each other and with the
+                        // class shorthand
+                        "class" | "style" => continue,
+                        _ => {}
+                    }
+                    name
+                }
             };
             let entry = attr_map.entry(name).or_default();
             entry.push(attr.span());
@@ -723,13 +1553,335 @@ impl Parser {
             if spans.len() > 1 {
                 let mut spans = spans.into_iter();
                 let first_span = spans.next().expect("spans should be non-empty");
+                if name == "id" {
+                    abort!(
+                        first_span,
+                        "duplicate attribute `id`";
+                        help = "to choose between ids, make all but one of them conditional: `#(id)[cond]`"
+                    );
+                }
                 abort!(first_span, "duplicate attribute `{}`", name);
             }
         }
 
+        // An attribute set by a branch can't be set anywhere else, as there
+        // would be no way to merge the two
+        let mut outside_names: Vec<String> =
+            attrs.iter().filter_map(ast::Attr::static_name).collect();
+        for attr in &attrs {
+            let ast::Attr::Branches { branches, .. } = attr else {
+                continue;
+            };
+            let mut branch_names = Vec::new();
+            for branch_attr in branches.iter().flat_map(|branch| &branch.attrs) {
+                let Some(name) = branch_attr.static_name() else {
+                    continue;
+                };
+                if outside_names.contains(&name) {
+                    if name == "class" {
+                        abort!(
+                            branch_attr.span(),
+                            "`class` is already set outside this branch";
+                            help = "to add a class on a condition, use a toggle instead: `.name[cond]`"
+                        );
+                    }
+                    abort!(
+                        branch_attr.span(),
+                        "attribute `{}` is already set outside this branch",
+                        name
+                    );
+                }
+                branch_names.push(name);
+            }
+            outside_names.extend(branch_names);
+        }
+
+        if let (Some(conditional_id), Some(named_id)) = (conditional_id, named_id) {
+            abort!(
+                named_id,
+                "`id` attribute can't be combined with a conditional id";
+                help = "use the shorthand instead: `#(id)`";
+                note = conditional_id.first => "the conditional id is here"
+            );
+        }
+
         attrs
     }
 
+    /// Aborts if a utility class list or variant group is followed by a
+    /// toggler, which would be ambiguous.
+    fn reject_class_list_toggler(&mut self) {
+        if let Some(TokenTree::Group(toggler)) = self.peek() {
+            if toggler.delimiter() == Delimiter::Bracket {
+                abort!(
+                    toggler,
+                    "a class list can't be toggled";
+                    help = "toggle each class on its own: `.\"name\"[cond]`"
+                );
+            }
+        }
+    }
+
+    /// Parses an `@if` in attribute position, along with any `@else if` and
+    /// `@else` branches.
+    ///
+    /// The leading `@if` should already be consumed.
+    fn attr_if(&mut self, at_span: Span, if_keyword: Ident) -> ast::Attr {
+        let mut branches = Vec::new();
+        let mut head = vec![TokenTree::Ident(if_keyword)];
+        loop {
+            let is_else = head.len() == 1
+                && matches!(head[0], TokenTree::Ident(ref ident) if ident == "else");
+            let (attrs, body_span) = self.attr_branch_body(at_span, &mut head);
+            branches.push(ast::AttrBranch {
+                head: head.drain(..).collect(),
+                attrs,
+                body_span,
+            });
+            if is_else {
+                break;
+            }
+            match self.peek2() {
+                Some((TokenTree::Punct(ref punct), Some(TokenTree::Ident(ref else_keyword))))
+                    if punct.as_char() == '@' && *else_keyword == "else" =>
+                {
+                    head.push(TokenTree::Ident(else_keyword.clone()));
+                    self.advance2();
+                    if let Some(TokenTree::Ident(ref if_keyword)) = self.peek() {
+                        if *if_keyword == "if" {
+                            head.push(TokenTree::Ident(if_keyword.clone()));
+                            self.advance();
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        let span = branches
+            .last()
+            .expect("branches should be non-empty")
+            .body_span;
+        ast::Attr::Branches {
+            at_span: SpanRange::single_span(at_span),
+            head: TokenStream::new(),
+            branches,
+            span,
+        }
+    }
+
+    /// Parses a `@match` in attribute position.
+    ///
+    /// The leading `@match` should already be consumed.
+    fn attr_match(&mut self, at_span: Span, match_keyword: Ident) -> ast::Attr {
+        let mut head = vec![TokenTree::Ident(match_keyword)];
+        let arms_group = loop {
+            match self.next() {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                    break group
+                }
+                Some(token) => head.push(token),
+                None => {
+                    let mut span = ast::span_tokens(head);
+                    span.first = at_span;
+                    abort!(span, "expected body for this `@match`");
+                }
+            }
+        };
+        let mut arms = self.with_input(arms_group.stream());
+        let mut branches = Vec::new();
+        loop {
+            let mut arm_head = Vec::new();
+            loop {
+                match arms.peek2() {
+                    Some((TokenTree::Punct(ref eq), Some(TokenTree::Punct(ref gt))))
+                        if eq.as_char() == '='
+                            && gt.as_char() == '>'
+                            && eq.spacing() == Spacing::Joint =>
+                    {
+                        arm_head.push(TokenTree::Punct(eq.clone()));
+                        arm_head.push(TokenTree::Punct(gt.clone()));
+                        arms.advance2();
+                        break;
+                    }
+                    Some((token, _)) => {
+                        arm_head.push(token.clone());
+                        arms.advance();
+                    }
+                    None if arm_head.is_empty() => break,
+                    None => {
+                        let head_span = ast::span_tokens(arm_head);
+                        abort!(head_span, "unexpected end of @match pattern");
+                    }
+                }
+            }
+            if arm_head.is_empty() {
+                break;
+            }
+            let (attrs, body_span) = match arms.next() {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => (
+                    arms.attr_block(&group),
+                    SpanRange::single_span(group.span()),
+                ),
+                Some(token) => abort!(
+                    token,
+                    "expected attributes in braces";
+                    help = "wrap the attributes for this arm in braces: `{ ... }`"
+                ),
+                None => {
+                    let head_span = ast::span_tokens(arm_head);
+                    abort!(head_span, "expected attributes for this arm");
+                }
+            };
+            if let Some(TokenTree::Punct(ref punct)) = arms.peek() {
+                if punct.as_char() == ',' {
+                    arms.advance();
+                }
+            }
+            branches.push(ast::AttrBranch {
+                head: arm_head.into_iter().collect(),
+                attrs,
+                body_span,
+            });
+        }
+        ast::Attr::Branches {
+            at_span: SpanRange::single_span(at_span),
+            head: head.into_iter().collect(),
+            branches,
+            span: SpanRange::single_span(arms_group.span()),
+        }
+    }
+
+    /// Parses the condition and body of one branch of an `@if` in attribute
+    /// position, adding the condition to `head`.
+    fn attr_branch_body(
+        &mut self,
+        at_span: Span,
+        head: &mut Vec<TokenTree>,
+    ) -> (Vec<ast::Attr>, SpanRange) {
+        loop {
+            match self.next() {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                    return (
+                        self.attr_block(&group),
+                        SpanRange::single_span(group.span()),
+                    );
+                }
+                Some(token) => head.push(token),
+                None => {
+                    let mut span = ast::span_tokens(head.clone());
+                    span.first = at_span;
+                    abort!(span, "expected attributes in braces after this");
+                }
+            }
+        }
+    }
+
+    /// Parses the attributes in the body of an `@if` or `@match` arm.
+    fn attr_block(&self, group: &Group) -> Vec<ast::Attr> {
+        let mut parser = self.with_input(group.stream());
+        let attrs = parser.attrs();
+        if let Some(token) = parser.next() {
+            abort!(
+                token,
+                "expected attribute";
+                help = "only attributes can go in an `@if` or `@match` before the element body"
+            );
+        }
+        attrs
+    }
+
+    /// Parses the value of an attribute, if any, along with its toggler.
+    ///
+    /// The attribute name should already be consumed.
+    fn attr_type(&mut self, attr_name: String) -> ast::AttrType {
+        match self.peek() {
+            // Non-empty attribute
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == '=' => {
+                self.advance();
+                // Parse a value under an attribute context
+                assert!(self.current_attr.is_none());
+                self.current_attr = Some(attr_name);
+                let attr_type = match self.attr_toggler() {
+                    Some(toggler) => ast::AttrType::Optional { toggler },
+                    None => {
+                        let value = self.markup();
+                        ast::AttrType::Normal { value }
+                    }
+                };
+                self.current_attr = None;
+                attr_type
+            }
+            // Empty attribute (legacy syntax)
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == '?' => {
+                self.advance();
+                let toggler = self.attr_toggler();
+                ast::AttrType::Empty { toggler }
+            }
+            // Empty attribute (new syntax)
+            _ => {
+                let toggler = self.attr_toggler();
+                ast::AttrType::Empty { toggler }
+            }
+        }
+    }
+
+    /// Parses an attribute name, which may contain splices, e.g.
+    /// `hx-(verb)` or `(name)`.
+    fn try_attr_name(&mut self) -> Option<AttrName> {
+        let mut parts = Vec::new();
+        loop {
+            if self.peek_parens() {
+                let group = match self.next() {
+                    Some(TokenTree::Group(group)) => group,
+                    _ => unreachable!(),
+                };
+                parts.push(ast::Markup::Splice {
+                    expr: group.stream(),
+                    outer_span: SpanRange::single_span(group.span()),
+                });
+                // A splice can be followed by more of the name, e.g. `(prefix)-id`
+                match self.peek() {
+                    Some(TokenTree::Punct(ref punct)) if punct.as_char() == '-' => {
+                        let hyphen = TokenTree::Punct(punct.clone());
+                        self.advance();
+                        parts.push(ast::Markup::Symbol {
+                            symbol: hyphen.into(),
+                        });
+                    }
+                    _ => break,
+                }
+            } else {
+                let name = if parts.is_empty() {
+                    self.try_namespaced_name()
+                } else {
+                    self.try_name()
+                };
+                let name = match name {
+                    Some(name) => name,
+                    None => break,
+                };
+                // `try_name` leaves a trailing hyphen if the name continues
+                // with a splice, e.g. `hx-(verb)`
+                let continues = matches!(
+                    name.clone().into_iter().last(),
+                    Some(TokenTree::Punct(ref punct)) if punct.as_char() == '-'
+                ) && self.peek_parens();
+                parts.push(ast::Markup::Symbol { symbol: name });
+                if !continues {
+                    break;
+                }
+            }
+        }
+        match parts.len() {
+            0 => None,
+            1 if matches!(parts[0], ast::Markup::Symbol { .. }) => match parts.pop() {
+                Some(ast::Markup::Symbol { symbol }) => Some(AttrName::Static(symbol)),
+                _ => unreachable!(),
+            },
+            _ => Some(AttrName::Dynamic(parts)),
+        }
+    }
+
     /// Parses the name of a class or ID.
     fn class_or_id_name(&mut self) -> ast::Markup {
         if let Some(symbol) = self.try_name() {
@@ -739,6 +1891,37 @@ impl Parser {
         }
     }
 
+    /// Parses the rest of a variant group in class shorthand, after its
+    /// first name: any more `name:` segments, then a `:(...)` group.
+    ///
+    /// Returns the prefix, e.g. `md:hover` for `.md:hover:(a b)`, and the
+    /// group. Nothing is consumed if this isn't a variant group.
+    fn variant_group(&mut self, first: &TokenStream) -> Option<(String, Group)> {
+        let mut fork = self.clone();
+        let mut prefix = ast::name_to_string(first.clone());
+        loop {
+            match fork.peek2() {
+                Some((TokenTree::Punct(colon), Some(TokenTree::Group(group))))
+                    if colon.as_char() == ':' && group.delimiter() == Delimiter::Parenthesis =>
+                {
+                    let group = group.clone();
+                    fork.advance2();
+                    *self = fork;
+                    return Some((prefix, group));
+                }
+                Some((TokenTree::Punct(colon), Some(TokenTree::Ident(_))))
+                    if colon.as_char() == ':' =>
+                {
+                    fork.advance();
+                    let segment = fork.try_name()?;
+                    prefix.push(':');
+                    prefix.push_str(&ast::name_to_string(segment));
+                }
+                _ => return None,
+            }
+        }
+    }
+
     /// Parses the `[cond]` syntax after an empty attribute or class shorthand.
     fn attr_toggler(&mut self) -> Option<ast::Toggler> {
         match self.peek() {
@@ -812,3 +1995,172 @@ impl Parser {
         }
     }
 }
+
+/// Returns `true` if `group` is a list of utility classes like
+/// `{m[1rem] hover:bg-red}`, rather than a block like `{ "a-" (b) }`.
+///
+/// Blocks start with a string literal, a splice, a nested block or a `@`.
+fn is_utility_class_list(group: &Group) -> bool {
+    if group.delimiter() != Delimiter::Brace {
+        return false;
+    }
+    match group.stream().into_iter().next() {
+        None => false,
+        Some(TokenTree::Literal(literal)) => !matches!(Lit::new(literal), Lit::Str(_)),
+        Some(TokenTree::Group(group)) => group.delimiter() == Delimiter::Bracket,
+        Some(TokenTree::Punct(punct)) => punct.as_char() != '@',
+        Some(TokenTree::Ident(_)) => true,
+    }
+}
+
+/// Splits a utility class list into its classes.
+///
+/// Tokens don't record whether there's whitespace between them, but two
+/// names (identifiers or numbers) can only follow each other directly if
+/// there was some. So a class ends wherever one name follows another, while
+/// punctuation and `[...]` join onto the name before them. A string
+/// literal is a class of its own, for classes that start with punctuation.
+/// Comments never become tokens, so they're left out. Each class is spanned
+/// to the tokens it was written with.
+fn utility_classes(group: &Group) -> Vec<ast::Markup> {
+    class_list(group.stream())
+        .into_iter()
+        .map(|(content, span)| ast::Markup::Literal { content, span })
+        .collect()
+}
+
+/// Splits `tokens` into classes, as described in [`utility_classes`].
+/// Variant groups like `hover:(a b)` are expanded, and can be nested.
+fn class_list(tokens: TokenStream) -> Vec<(String, SpanRange)> {
+    let mut classes = Vec::new();
+    let mut current: Option<(String, SpanRange)> = None;
+    let mut after_name = false;
+    for token in flatten_none_groups(tokens) {
+        let span = token.span();
+        match token {
+            TokenTree::Literal(ref literal) => {
+                if let Lit::Str(lit_str) = Lit::new(literal.clone()) {
+                    classes.extend(current.take());
+                    classes.push((lit_str.value(), SpanRange::single_span(span)));
+                    after_name = false;
+                    continue;
+                }
+            }
+            TokenTree::Group(ref group) if group.delimiter() == Delimiter::Parenthesis => {
+                let (prefix, prefix_span) = match current.take() {
+                    Some((prefix, prefix_span)) => (prefix, Some(prefix_span)),
+                    None => (String::new(), None),
+                };
+                for (class, class_span) in class_list(group.stream()) {
+                    let first = prefix_span.map_or(class_span.first, |prefix| prefix.first);
+                    classes.push((
+                        format!("{prefix}{class}"),
+                        SpanRange {
+                            first,
+                            last: class_span.last,
+                        },
+                    ));
+                }
+                after_name = false;
+                continue;
+            }
+            _ => {}
+        }
+        let is_name = !matches!(token, TokenTree::Punct(_));
+        // A `[...]` belongs to the name before it, like in `m[1rem]`
+        let is_bracket = matches!(token, TokenTree::Group(_));
+        if is_name && after_name && !is_bracket {
+            classes.extend(current.take());
+        }
+        let (text, class_span) =
+            current.get_or_insert_with(|| (String::new(), SpanRange::single_span(span)));
+        push_token_text(&token, text);
+        class_span.last = span;
+        after_name = is_name;
+    }
+    classes.extend(current);
+    classes
+}
+
+/// Replaces groups with no delimiters, e.g. from a `macro_rules!`
+/// fragment, with their contents.
+fn flatten_none_groups(tokens: TokenStream) -> Vec<TokenTree> {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+                flatten_none_groups(group.stream())
+            }
+            token => vec![token],
+        })
+        .collect()
+}
+
+/// Appends the text of `token` to `text`, without any whitespace.
+fn push_token_text(token: &TokenTree, text: &mut String) {
+    match token {
+        TokenTree::Group(group) => {
+            let (open, close) = match group.delimiter() {
+                Delimiter::Parenthesis => ("(", ")"),
+                Delimiter::Bracket => ("[", "]"),
+                Delimiter::Brace => ("{", "}"),
+                Delimiter::None => ("", ""),
+            };
+            text.push_str(open);
+            for token in group.stream() {
+                push_token_text(&token, text);
+            }
+            text.push_str(close);
+        }
+        token => text.push_str(&token.to_string()),
+    }
+}
+
+/// Parses a capture in a `@format` string, like `name` or `user.name`,
+/// giving its identifiers the span `span`.
+fn format_capture(capture: &str, span: Span) -> Option<TokenStream> {
+    let mut tokens = Vec::new();
+    for (index, segment) in capture.trim().split('.').enumerate() {
+        if index > 0 {
+            tokens.push(TokenTree::Punct(Punct::new('.', Spacing::Alone)));
+        }
+        let is_ident = segment.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && segment.chars().all(|c| c.is_alphanumeric() || c == '_');
+        let is_tuple_field =
+            index > 0 && !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+        if is_ident {
+            tokens.push(TokenTree::Ident(Ident::new(segment, span)));
+        } else if is_tuple_field {
+            let mut literal = Literal::usize_unsuffixed(segment.parse().ok()?);
+            literal.set_span(span);
+            tokens.push(TokenTree::Literal(literal));
+        } else {
+            return None;
+        }
+    }
+    Some(tokens.into_iter().collect())
+}
+
+/// Finds the span of a byte range of the concatenated values of some string
+/// literals, given along with the offsets where they start.
+///
+/// This points into the literal if the compiler supports it, and if the
+/// literal has no escapes that would throw the offsets off. Otherwise it
+/// points at the whole literal.
+fn literal_span(literals: &[(usize, Literal)], range: Range<usize>) -> Option<Span> {
+    let (start, literal) = literals
+        .iter()
+        .rev()
+        .find(|(start, _)| *start <= range.start)?;
+    let source = literal.to_string();
+    let is_raw_string = source.starts_with('r');
+    if !is_raw_string && source.contains('\\') {
+        return Some(literal.span());
+    }
+    // Skip the `"` or `r#"`, and don't go past the closing quote
+    let prefix = source.find('"')? + 1;
+    let suffix = if is_raw_string { prefix - 1 } else { 1 };
+    let end = (prefix + range.end - start).min(source.len() - suffix);
+    let subspan = literal.subspan(prefix + range.start - start..end);
+    Some(subspan.unwrap_or_else(|| literal.span()))
+}
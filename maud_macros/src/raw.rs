@@ -0,0 +1,127 @@
+//! A rough well-formedness check for `@raw` blocks.
+//!
+//! This isn't a full HTML parser. It makes sure that tags are closed and
+//! balanced, and that comments and quoted attribute values are terminated,
+//! which catches most copy-and-paste accidents.
+
+use std::ops::Range;
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose content is text, and can't contain other tags.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Checks that `html` is well-formed, returning the byte range of the
+/// first problem along with a description of it.
+pub fn check(html: &str) -> Result<(), (Range<usize>, String)> {
+    let mut open: Vec<(String, Range<usize>)> = Vec::new();
+    let mut i = 0;
+    while let Some(offset) = html[i..].find('<') {
+        let start = i + offset;
+        let rest = &html[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment
+                .find("-->")
+                .ok_or_else(|| (start..start + 4, "unclosed comment".to_owned()))?;
+            i = start + 4 + end + 3;
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest
+                .find('>')
+                .ok_or_else(|| (start..start + 2, "unclosed declaration".to_owned()))?;
+            i = start + end + 1;
+        } else if let Some(tag) = rest.strip_prefix("</") {
+            let name_len = name_len(tag);
+            if name_len == 0 {
+                return Err((
+                    start..start + 2,
+                    "expected element name after `</`".to_owned(),
+                ));
+            }
+            let name = tag[..name_len].to_ascii_lowercase();
+            let end = tag.find('>').ok_or_else(|| {
+                (
+                    start..start + 2 + name_len,
+                    format!("unclosed tag `</{name}`"),
+                )
+            })?;
+            let tag_range = start..start + 2 + end + 1;
+            match open.pop() {
+                Some((open_name, _)) if open_name == name => {}
+                Some((open_name, _)) => {
+                    return Err((
+                        tag_range,
+                        format!("`</{name}>` doesn't match the open `<{open_name}>`"),
+                    ))
+                }
+                None => return Err((tag_range, format!("unexpected closing tag `</{name}>`"))),
+            }
+            i = tag_range.end;
+        } else {
+            let tag = &rest[1..];
+            let name_len = name_len(tag);
+            if name_len == 0 {
+                return Err((
+                    start..start + 1,
+                    "stray `<`, write `&lt;` instead".to_owned(),
+                ));
+            }
+            let name = tag[..name_len].to_ascii_lowercase();
+            let end = tag_end(tag, name_len).ok_or_else(|| {
+                (
+                    start..start + 1 + name_len,
+                    format!("unclosed tag `<{name}`"),
+                )
+            })?;
+            let tag_range = start..start + 1 + end + 1;
+            i = tag_range.end;
+            if VOID_ELEMENTS.contains(&name.as_str()) || tag[..end].ends_with('/') {
+                continue;
+            }
+            if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+                // Skip to the closing tag, which is then handled as usual
+                let close = format!("</{name}");
+                match html[i..].to_ascii_lowercase().find(&close) {
+                    Some(offset) => i += offset,
+                    None => return Err((tag_range, format!("unclosed `<{name}>`"))),
+                }
+            }
+            open.push((name, tag_range));
+        }
+    }
+    match open.pop() {
+        Some((name, range)) => Err((range, format!("unclosed `<{name}>`"))),
+        None => Ok(()),
+    }
+}
+
+/// Returns the length of the element name at the start of `s`, or 0 if
+/// there isn't one.
+fn name_len(s: &str) -> usize {
+    match s.bytes().next() {
+        Some(b) if b.is_ascii_alphabetic() => s
+            .bytes()
+            .take_while(|&b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+            .count(),
+        _ => 0,
+    }
+}
+
+/// Returns the index of the `>` that ends a start tag, skipping over quoted
+/// attribute values.
+fn tag_end(tag: &str, name_len: usize) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tag.char_indices().skip(name_len) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(index),
+            (None, '<') => return None,
+            (None, _) => {}
+        }
+    }
+    None
+}
@@ -0,0 +1,72 @@
+//! Whitespace handling for `@text` blocks.
+
+/// Removes the indentation shared by all non-blank lines of `text`.
+///
+/// A leading line break and a trailing line of only whitespace are removed
+/// too, so that the text can start and end on its own lines:
+///
+/// ```text
+/// @text { r#"
+///     Hello,
+///       world!
+///     "# }
+/// ```
+///
+/// renders as `"Hello,\n  world!"`. Trailing whitespace on each line is
+/// dropped as well.
+pub fn dedent(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    if lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+    if lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| strip_indent(line, indent).trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Joins the lines of each paragraph with single spaces.
+///
+/// Paragraphs are separated by blank lines, and are kept apart by one blank
+/// line in the output.
+pub fn reflow(text: &str) -> String {
+    let mut paragraphs = Vec::new();
+    let mut words: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !words.is_empty() {
+                paragraphs.push(words.join(" "));
+                words.clear();
+            }
+        } else {
+            words.extend(line.split_whitespace());
+        }
+    }
+    if !words.is_empty() {
+        paragraphs.push(words.join(" "));
+    }
+    paragraphs.join("\n\n")
+}
+
+/// Removes up to `indent` whitespace characters from the start of `line`.
+fn strip_indent(line: &str, indent: usize) -> &str {
+    let mut rest = line;
+    for _ in 0..indent {
+        let mut chars = rest.chars();
+        match chars.next() {
+            Some(c) if c.is_whitespace() => rest = chars.as_str(),
+            _ => break,
+        }
+    }
+    rest
+}
@@ -1,41 +1,219 @@
-use proc_macro2::TokenStream;
+use std::collections::HashMap;
+#[cfg(feature = "zephyr_build")]
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+#[cfg(feature = "zephyr_build")]
+use proc_macro_error::emit_call_site_error;
+#[cfg(any(feature = "zephyr", feature = "zephyr_build"))]
 use quote::quote;
+use syn::Lit;
 
 use crate::ast::*;
 
-pub fn zephyr(markups: &[Markup]) -> Option<TokenStream> {
-    let all_lit_classes = markups
+/// The string literals assigned to each simple `@let` binding, e.g.
+/// `@let c = if x { "a" } else { "b" };`.
+type Lets = HashMap<String, Vec<String>>;
+
+/// Returns every class in the template that's known at compile time,
+/// separated by spaces.
+pub fn classes(markups: &[Markup]) -> String {
+    let mut lets = Lets::new();
+    for markup in markups {
+        collect_lets(markup, &mut lets);
+    }
+    let mut component_classes = Vec::new();
+    for markup in markups {
+        collect_component_classes(markup, &lets, &mut component_classes);
+    }
+    markups
         .iter()
         .flat_map(|m| get_single_or_block_element_attrs(m))
+        .flat_map(flatten_branches)
         .flat_map(|a| {
-            if let Attr::Class {
-                name,
-                toggler: None,
-                ..
-            } = a
-            {
-                return get_single_or_block_literal(name);
+            // Toggled classes are registered too, since the name is known
+            // even if the condition isn't
+            if let Attr::Class { name, .. } = a {
+                return get_single_or_block_literal(name, &lets);
             } else if let Attr::Named { named_attr } = a {
-                if let AttrType::Normal { value } = &named_attr.attr_type {
-                    if name_to_string(named_attr.name.clone()).trim() == "class" {
-                        return get_single_or_block_literal(value);
+                if name_to_string(named_attr.name.clone()).trim() == "class" {
+                    match &named_attr.attr_type {
+                        AttrType::Normal { value } => {
+                            return get_single_or_block_literal(value, &lets)
+                        }
+                        AttrType::Optional { toggler } => {
+                            let mut literals = Vec::new();
+                            expr_literals(toggler.cond.clone(), &lets, &mut literals);
+                            return Some(literals.join(" "));
+                        }
+                        AttrType::Empty { .. } => {}
                     }
                 }
             }
             None
         })
+        .chain(component_classes)
+        .filter(|classes| !classes.is_empty())
         .collect::<Vec<_>>()
-        .join(" ");
+        .join(" ")
+}
 
-    if !all_lit_classes.is_empty() {
+/// Registers `classes` with the zephyr inventory.
+#[cfg(feature = "zephyr")]
+pub fn register(classes: &str) -> Option<TokenStream> {
+    if !classes.is_empty() {
         Some(quote! {
-            maud::zephyr::register_class!(#all_lit_classes);
+            maud::zephyr::register_class!(#classes);
         })
     } else {
         None
     }
 }
 
+/// The classes recorded so far while compiling this crate, or `None` before
+/// the first template.
+#[cfg(feature = "zephyr_build")]
+static RECORDED: Mutex<Option<BTreeSet<String>>> = Mutex::new(None);
+
+/// Writes `classes` to a file for `maud::zephyr::build` to pick up.
+///
+/// Each crate gets one file, which holds the classes from all of its
+/// templates. The first template expanded by the compiler overwrites it,
+/// so classes from templates that have since changed don't linger.
+///
+/// rust-analyzer expands templates as they're typed, and keeps the macro
+/// loaded across edits, so nothing is recorded while running under it.
+/// Test builds aren't recorded either, since their templates aren't part
+/// of what ships, and they'd otherwise overwrite the file of the library
+/// they test.
+#[cfg(feature = "zephyr_build")]
+pub fn record(classes: &str) {
+    if env::var_os("RUST_ANALYZER_INTERNALS_DO_NOT_USE").is_some() || is_test_build() {
+        return;
+    }
+    let mut recorded = RECORDED.lock().unwrap_or_else(PoisonError::into_inner);
+    let first = recorded.is_none();
+    let mut changed = first;
+    let recorded = recorded.get_or_insert_with(BTreeSet::new);
+    for class in classes.split_whitespace() {
+        changed |= recorded.insert(class.to_owned());
+    }
+    if !changed {
+        return;
+    }
+
+    let dir = match classes_dir() {
+        Ok(dir) => dir,
+        Err(message) => {
+            // Once per crate is enough
+            if first {
+                emit_call_site_error!(
+                    "{}", message;
+                    help = "set it in `.cargo/config.toml`, under `[env]`: \
+                        `MAUD_ZEPHYR_DIR = { value = \"target/maud-zephyr\", relative = true }`"
+                );
+            }
+            return;
+        }
+    };
+    let package = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "unknown".to_owned());
+    let crate_name = env::var("CARGO_CRATE_NAME").unwrap_or_else(|_| "unknown".to_owned());
+    // A binary can have the same name as the library in its package
+    let file_name = match env::var_os("CARGO_BIN_NAME") {
+        Some(_) => format!("bin-{crate_name}.txt"),
+        None => format!("{crate_name}.txt"),
+    };
+    let dir = dir.join(package);
+    let contents = recorded
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Err(error) =
+        fs::create_dir_all(&dir).and_then(|()| fs::write(dir.join(file_name), contents))
+    {
+        emit_call_site_error!(
+            "couldn't record classes in `{}`: {}", dir.display(), error;
+            help = "set `MAUD_ZEPHYR_DIR` to a writable directory"
+        );
+    }
+}
+
+/// Returns whether the crate being compiled is a test harness, from
+/// `cargo test` or `--all-targets`.
+#[cfg(feature = "zephyr_build")]
+fn is_test_build() -> bool {
+    // Proc macros run inside the compiler, so these are its arguments.
+    // Cargo may pass some of them in an `@file` instead
+    env::args().any(|arg| {
+        arg == "--test"
+            || arg
+                .strip_prefix('@')
+                .and_then(|path| fs::read_to_string(path).ok())
+                .is_some_and(|args| args.lines().any(|arg| arg == "--test"))
+    })
+}
+
+/// Returns the directory that classes are recorded in, which is
+/// `MAUD_ZEPHYR_DIR`.
+///
+/// The compiler runs in a different directory for each dependency, and
+/// Cargo doesn't tell it where the target directory is, so there's no
+/// reliable default, and a relative path would mean something different
+/// for each crate. `maud::zephyr::classes_dir` gets it from
+/// [`classes_dir_literal`], so the two always agree.
+#[cfg(feature = "zephyr_build")]
+fn classes_dir() -> Result<PathBuf, String> {
+    match env::var_os("MAUD_ZEPHYR_DIR") {
+        Some(dir) if Path::new(&dir).is_absolute() => Ok(PathBuf::from(dir)),
+        Some(dir) => Err(format!(
+            "`MAUD_ZEPHYR_DIR` must be an absolute path, but it's `{}`",
+            Path::new(&dir).display()
+        )),
+        None => Err("`MAUD_ZEPHYR_DIR` isn't set, so there's nowhere to record classes".to_owned()),
+    }
+}
+
+/// Expands to [`classes_dir`] as a `Result<&str, &str>`, where the error is
+/// the message explaining what's wrong.
+///
+/// The expansion mentions `MAUD_ZEPHYR_DIR` with `option_env!`, so that the
+/// compiler rebuilds the caller when it changes.
+#[cfg(feature = "zephyr_build")]
+pub fn classes_dir_literal() -> TokenStream {
+    let dir = classes_dir().and_then(|dir| match dir.to_str() {
+        Some(dir) => Ok(dir.to_owned()),
+        None => Err(format!("`{}` isn't valid UTF-8", dir.display())),
+    });
+    let result = match dir {
+        Ok(dir) => quote!(::core::result::Result::Ok(#dir)),
+        Err(message) => quote!(::core::result::Result::Err(#message)),
+    };
+    quote!({
+        let _ = ::core::option_env!("MAUD_ZEPHYR_DIR");
+        let dir: ::core::result::Result<&str, &str> = #result;
+        dir
+    })
+}
+
+/// Replaces an `@if` or `@match` in attribute position with the attributes
+/// from all of its branches.
+fn flatten_branches(attr: &Attr) -> Vec<&Attr> {
+    match attr {
+        Attr::Branches { branches, .. } => branches
+            .iter()
+            .flat_map(|branch| &branch.attrs)
+            .flat_map(flatten_branches)
+            .collect(),
+        attr => vec![attr],
+    }
+}
+
 fn get_single_or_block_element_attrs(markup: &Markup) -> Vec<&Attr> {
     match markup {
         Markup::Block(block) => block
@@ -43,7 +221,7 @@ fn get_single_or_block_element_attrs(markup: &Markup) -> Vec<&Attr> {
             .iter()
             .flat_map(get_single_or_block_element_attrs)
             .collect::<Vec<_>>(),
-        Markup::Element { attrs, body, .. } => {
+        Markup::Element { attrs, body, .. } | Markup::DynamicElement { attrs, body, .. } => {
             let mut out: Vec<_> = attrs.iter().collect();
             if let ElementBody::Block { block } = body {
                 out.extend(
@@ -56,17 +234,46 @@ fn get_single_or_block_element_attrs(markup: &Markup) -> Vec<&Attr> {
 
             out
         }
-        Markup::Custom {
-            body: ElementBody::Block { block },
-            ..
-        } => block
-            .markups
+        Markup::Custom { attrs, body, .. } => {
+            let mut out: Vec<_> = attrs.iter().flatten().collect();
+            if let ElementBody::Block { block } = body {
+                out.extend(
+                    block
+                        .markups
+                        .iter()
+                        .flat_map(get_single_or_block_element_attrs),
+                )
+            }
+
+            out
+        }
+        Markup::Special { segments } => segments
             .iter()
+            .flat_map(|s| &s.body.markups)
             .flat_map(get_single_or_block_element_attrs)
             .collect(),
-        Markup::Special { segments } => segments
+        Markup::For {
+            body,
+            separator,
+            empty,
+            ..
+        } => [Some(body), separator.as_ref(), empty.as_ref()]
+            .into_iter()
+            .flatten()
+            .flat_map(|block| &block.markups)
+            .flat_map(get_single_or_block_element_attrs)
+            .collect(),
+        Markup::Let {
+            else_body: Some(body),
+            ..
+        }
+        | Markup::LocalFn { body, .. }
+        | Markup::LocalCall {
+            body: ElementBody::Block { block: body },
+            ..
+        } => body
+            .markups
             .iter()
-            .flat_map(|s| &s.body.markups)
             .flat_map(get_single_or_block_element_attrs)
             .collect(),
         Markup::Match { arms, .. } => arms
@@ -78,17 +285,38 @@ fn get_single_or_block_element_attrs(markup: &Markup) -> Vec<&Attr> {
     }
 }
 
-fn get_single_or_block_literal(markup: &Markup) -> Option<String> {
+fn get_single_or_block_literal(markup: &Markup, lets: &Lets) -> Option<String> {
     match markup {
         Markup::Block(block) => Some(
             block
                 .markups
                 .iter()
-                .flat_map(get_single_or_block_literal)
+                .flat_map(|markup| get_single_or_block_literal(markup, lets))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        // Every branch of an `@if` or `@match` could be taken
+        Markup::Special { segments } => Some(
+            segments
+                .iter()
+                .flat_map(|segment| &segment.body.markups)
+                .flat_map(|markup| get_single_or_block_literal(markup, lets))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        Markup::Match { arms, .. } => Some(
+            arms.iter()
+                .flat_map(|arm| &arm.body.markups)
+                .flat_map(|markup| get_single_or_block_literal(markup, lets))
                 .collect::<Vec<_>>()
                 .join(" "),
         ),
         Markup::Literal { content, .. } => Some(content.to_string()),
+        Markup::Splice { expr, .. } => {
+            let mut literals = Vec::new();
+            expr_literals(expr.clone(), lets, &mut literals);
+            Some(literals.join(" "))
+        }
         Markup::Symbol { symbol } => Some(
             symbol
                 .clone()
@@ -99,3 +327,299 @@ fn get_single_or_block_literal(markup: &Markup) -> Option<String> {
         _ => None,
     }
 }
+
+/// Returns the markups directly inside `markup`.
+fn children(markup: &Markup) -> Vec<&Markup> {
+    let blocks: Vec<&Block> = match markup {
+        Markup::Block(block) => vec![block],
+        Markup::Element { body, .. }
+        | Markup::DynamicElement { body, .. }
+        | Markup::Custom { body, .. }
+        | Markup::LocalCall { body, .. } => match body {
+            ElementBody::Block { block } => vec![block],
+            ElementBody::Void { .. } => vec![],
+        },
+        Markup::Let {
+            else_body: Some(body),
+            ..
+        }
+        | Markup::LocalFn { body, .. } => vec![body],
+        Markup::Special { segments } => segments.iter().map(|segment| &segment.body).collect(),
+        Markup::For {
+            body,
+            separator,
+            empty,
+            ..
+        } => [Some(body), separator.as_ref(), empty.as_ref()]
+            .into_iter()
+            .flatten()
+            .collect(),
+        Markup::Match { arms, .. } => arms.iter().map(|arm| &arm.body).collect(),
+        _ => vec![],
+    };
+    blocks
+        .into_iter()
+        .flat_map(|block| &block.markups)
+        .collect()
+}
+
+/// Records the string literals assigned to simple `@let` bindings
+/// anywhere in the template.
+///
+/// Scopes aren't tracked, so a shadowed binding adds to the same entry.
+/// At worst, this registers a class that isn't used.
+fn collect_lets(markup: &Markup, lets: &mut Lets) {
+    if let Markup::Let { tokens, .. } = markup {
+        // `let` [`mut`] name `=` expr `;`
+        let mut tokens = tokens.clone().into_iter().skip(1).peekable();
+        if matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if ident == "mut") {
+            tokens.next();
+        }
+        if let (Some(TokenTree::Ident(name)), Some(TokenTree::Punct(eq))) =
+            (tokens.next(), tokens.next())
+        {
+            if eq.as_char() == '=' {
+                let mut expr = tokens.collect::<Vec<_>>();
+                if matches!(expr.last(), Some(TokenTree::Punct(semi)) if semi.as_char() == ';') {
+                    expr.pop();
+                }
+                let mut literals = Vec::new();
+                expr_literals(expr.into_iter().collect(), lets, &mut literals);
+                lets.entry(name.to_string()).or_default().extend(literals);
+            }
+        }
+    }
+    for child in children(markup) {
+        collect_lets(child, lets);
+    }
+}
+
+/// Collects the classes passed to components as `Class("...")`.
+fn collect_component_classes(markup: &Markup, lets: &Lets, classes: &mut Vec<String>) {
+    if let Markup::Custom { expr: args, .. } | Markup::LocalCall { args, .. } = markup {
+        class_args(args.clone(), lets, classes);
+    }
+    for child in children(markup) {
+        collect_component_classes(child, lets, classes);
+    }
+}
+
+fn class_args(args: TokenStream, lets: &Lets, classes: &mut Vec<String>) {
+    let mut tokens = args.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "Class" => {
+                if let Some(TokenTree::Group(group)) = tokens.peek() {
+                    expr_literals(group.stream(), lets, classes);
+                    tokens.next();
+                }
+            }
+            TokenTree::Group(group) => class_args(group.stream(), lets, classes),
+            _ => {}
+        }
+    }
+}
+
+/// Collects the string literals that an expression can evaluate to, like
+/// both branches of `if x { "a" } else { "b" }`, along with those of any
+/// `@let` binding it names.
+///
+/// Only literals in tail position count: the expression itself, the tail
+/// of a block, each branch of an `if` or arm of a `match`, and the inside
+/// of `Some(...)`. Anything else, such as a method argument in
+/// `map.get("key")` or a `format!` string, isn't a class and is skipped.
+fn expr_literals(expr: TokenStream, lets: &Lets, literals: &mut Vec<String>) {
+    let tokens = expr.into_iter().collect::<Vec<_>>();
+    match strip_conversions(&tokens) {
+        [TokenTree::Literal(literal)] => {
+            if let Lit::Str(lit_str) = Lit::new(literal.clone()) {
+                literals.push(lit_str.value());
+            }
+        }
+        [TokenTree::Ident(ident)] => {
+            if let Some(values) = lets.get(&ident.to_string()) {
+                literals.extend(values.iter().cloned());
+            }
+        }
+        [TokenTree::Group(group)] => match group.delimiter() {
+            Delimiter::Brace => expr_literals(block_tail(group.stream()), lets, literals),
+            Delimiter::Parenthesis | Delimiter::None => {
+                expr_literals(group.stream(), lets, literals)
+            }
+            Delimiter::Bracket => {}
+        },
+        [TokenTree::Ident(ident), TokenTree::Group(group)]
+            if ident == "Some" && group.delimiter() == Delimiter::Parenthesis =>
+        {
+            expr_literals(group.stream(), lets, literals)
+        }
+        [TokenTree::Ident(ident), ..] if ident == "if" => {
+            // A branch is a block followed by `else`, or the final block
+            for (i, token) in tokens.iter().enumerate() {
+                if let TokenTree::Group(group) = token {
+                    let is_branch = match tokens.get(i + 1) {
+                        None => true,
+                        Some(TokenTree::Ident(next)) => next == "else",
+                        Some(_) => false,
+                    };
+                    if group.delimiter() == Delimiter::Brace && is_branch {
+                        expr_literals(block_tail(group.stream()), lets, literals);
+                    }
+                }
+            }
+        }
+        [TokenTree::Ident(ident), .., TokenTree::Group(arms)]
+            if ident == "match" && arms.delimiter() == Delimiter::Brace =>
+        {
+            match_arm_literals(arms.stream(), lets, literals)
+        }
+        _ => {}
+    }
+}
+
+/// Strips trailing `.into()`, `.to_owned()` and `.to_string()` calls, which
+/// don't change the value.
+fn strip_conversions(mut tokens: &[TokenTree]) -> &[TokenTree] {
+    while let [rest @ .., TokenTree::Punct(dot), TokenTree::Ident(method), TokenTree::Group(args)] =
+        tokens
+    {
+        if dot.as_char() == '.'
+            && (method == "into" || method == "to_owned" || method == "to_string")
+            && args.delimiter() == Delimiter::Parenthesis
+            && args.stream().is_empty()
+        {
+            tokens = rest;
+        } else {
+            break;
+        }
+    }
+    tokens
+}
+
+/// Returns the tokens after the last `;` in a block.
+fn block_tail(block: TokenStream) -> TokenStream {
+    let tokens = block.into_iter().collect::<Vec<_>>();
+    let start = tokens
+        .iter()
+        .rposition(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == ';'))
+        .map_or(0, |i| i + 1);
+    tokens[start..].iter().cloned().collect()
+}
+
+/// Collects the literals from the body of each arm of a `match`.
+fn match_arm_literals(arms: TokenStream, lets: &Lets, literals: &mut Vec<String>) {
+    let mut tokens = arms.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        // Skip the pattern and guard, up to the `=>`
+        let is_arrow = matches!(
+            &token,
+            TokenTree::Punct(punct) if punct.as_char() == '=' && punct.spacing() == Spacing::Joint
+        ) && matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == '>');
+        if !is_arrow {
+            continue;
+        }
+        tokens.next();
+        // A block body doesn't need a trailing comma
+        if let Some(TokenTree::Group(group)) = tokens.peek() {
+            if group.delimiter() == Delimiter::Brace {
+                let body = tokens.next().into_iter().collect();
+                expr_literals(body, lets, literals);
+                continue;
+            }
+        }
+        let mut body = Vec::new();
+        for token in tokens.by_ref() {
+            if matches!(&token, TokenTree::Punct(punct) if punct.as_char() == ',') {
+                break;
+            }
+            body.push(token);
+        }
+        expr_literals(body.into_iter().collect(), lets, literals);
+    }
+}
+#[cfg(test)]
+mod test {
+    use quote::quote;
+
+    use super::classes;
+    use crate::parse::parse;
+
+    #[test]
+    fn static_classes() {
+        let markups = parse(quote!(div.flex.p-2 { span class="text-red" {} }));
+        assert_eq!(classes(&markups), "flex p-2 text-red");
+    }
+
+    #[test]
+    fn toggled_classes() {
+        let markups = parse(quote!(div.hidden[!visible].bold[strong] {}));
+        assert_eq!(classes(&markups), "hidden bold");
+    }
+
+    #[test]
+    fn if_in_class_attribute() {
+        let markups = parse(quote!(div class={ "base " @if x { "on" } @else { "off" } } {}));
+        assert_eq!(classes(&markups), "base  on off");
+    }
+
+    #[test]
+    fn match_in_class_attribute() {
+        let markups = parse(quote! {
+            div class={ @match size { Size::Small => "text-sm", _ => "text-lg" } } {}
+        });
+        assert_eq!(classes(&markups), "text-sm text-lg");
+    }
+
+    #[test]
+    fn if_around_class_attribute() {
+        let markups = parse(quote!(div @if x { class="on" } @else { class="off" } {}));
+        assert_eq!(classes(&markups), "on off");
+    }
+
+    #[test]
+    fn if_expression_in_class_splice() {
+        let markups = parse(quote!(div class=(if x { "a" } else if y { "b" } else { "c" }) {}));
+        assert_eq!(classes(&markups), "a b c");
+    }
+
+    #[test]
+    fn match_expression_in_class_splice() {
+        let markups = parse(quote! {
+            div class=(match n { 0 => "zero", n if n < 0 => { "neg" } _ => "pos".into() }) {}
+        });
+        assert_eq!(classes(&markups), "zero neg pos");
+    }
+
+    #[test]
+    fn let_binding_in_class_splice() {
+        let markups = parse(quote! {
+            @let c = if x { "on" } else { "off" };
+            div class=(c) {}
+        });
+        assert_eq!(classes(&markups), "on off");
+    }
+
+    #[test]
+    fn component_class_arguments() {
+        let markups = parse(quote! {
+            @badge("New", Class("btn"));
+            @card(maud::Class(if x { "a" } else { "b" })) { "body" }
+        });
+        assert_eq!(classes(&markups), "btn a b");
+    }
+
+    #[test]
+    fn format_strings_skipped() {
+        let markups = parse(quote!(div class=(format!("w-{}", n)) .(format!("h-{n}")) {}));
+        assert_eq!(classes(&markups), "");
+    }
+
+    #[test]
+    fn non_tail_literals_skipped() {
+        let markups = parse(quote! {
+            div class=(map.get("key")) {}
+            div class=(if flags.contains("x") { "on" } else { "off" }) {}
+        });
+        assert_eq!(classes(&markups), "on off");
+    }
+}